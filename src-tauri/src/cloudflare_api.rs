@@ -0,0 +1,450 @@
+//! Minimal client for the Cloudflare v4 API, covering the tunnel setup steps the app
+//! can do on the user's behalf instead of sending them to the dashboard.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const API_BASE: &str = "https://api.cloudflare.com/client/v4";
+
+/// A named tunnel created through the API
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedTunnel {
+    pub id: String,
+    pub name: String,
+    /// Connector token to pass to `cloudflared tunnel run --token`
+    pub token: String,
+}
+
+/// Standard v4 response envelope
+#[derive(Deserialize)]
+struct ApiResponse<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<ApiError>,
+    result: Option<T>,
+    #[serde(default)]
+    result_info: Option<ResultInfo>,
+}
+
+/// Paging details on list endpoints
+#[derive(Deserialize)]
+struct ResultInfo {
+    #[serde(default)]
+    total_pages: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct TunnelResult {
+    id: String,
+    name: String,
+    #[serde(default)]
+    token: Option<String>,
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// API token permission the tunnel endpoints need
+const TUNNEL_PERMISSION: &str = "Cloudflare Tunnel: Edit";
+/// API token permission the zone and DNS record endpoints need
+const DNS_PERMISSION: &str = "Zone - DNS: Edit";
+
+async fn parse_response<T: serde::de::DeserializeOwned>(
+    response: reqwest::Response,
+    permission: &str,
+) -> Result<T, String> {
+    parse_page(response, permission).await.map(|(result, _)| result)
+}
+
+/// Turn a v4 response into its result and paging info, mapping the failures users
+/// actually hit to messages that say what to fix. `permission` is the token scope the
+/// endpoint needs, named when the API answers 403.
+async fn parse_page<T: serde::de::DeserializeOwned>(
+    response: reqwest::Response,
+    permission: &str,
+) -> Result<(T, Option<ResultInfo>), String> {
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read Cloudflare API response: {}", e))?;
+    let parsed: Option<ApiResponse<T>> = serde_json::from_str(&body).ok();
+
+    if let Some(ApiResponse { success: true, result: Some(result), result_info, .. }) = parsed {
+        return Ok((result, result_info));
+    }
+
+    let api_message = parsed
+        .as_ref()
+        .and_then(|r| r.errors.first())
+        .map(|e| format!("{} (code {})", e.message, e.code));
+
+    Err(match status.as_u16() {
+        400 if body.contains("already") => {
+            format!("Already exists: {}", api_message.unwrap_or_default())
+        }
+        401 => "Cloudflare API token is invalid or expired".to_string(),
+        403 => match api_message {
+            Some(message) => format!("Cloudflare API token lacks the \"{}\" permission: {}", permission, message),
+            None => format!("Cloudflare API token lacks the \"{}\" permission", permission),
+        },
+        404 => "Not found - check the account ID and that the resource still exists".to_string(),
+        429 => "Cloudflare API rate limit hit, try again in a minute".to_string(),
+        _ => match api_message {
+            Some(message) => format!("Cloudflare API error: {}", message),
+            None => format!("Cloudflare API returned {}", status),
+        },
+    })
+}
+
+/// Create a remotely-managed named tunnel and return it with its connector token
+pub async fn create_tunnel(name: &str, account_id: &str, api_token: &str) -> Result<CreatedTunnel, String> {
+    let name = name.trim();
+    let account_id = account_id.trim();
+    let api_token = api_token.trim();
+    if name.is_empty() || account_id.is_empty() || api_token.is_empty() {
+        return Err("Tunnel name, account ID and API token are required".to_string());
+    }
+
+    let client = client()?;
+    let response = client
+        .post(format!("{}/accounts/{}/cfd_tunnel", API_BASE, account_id))
+        .bearer_auth(api_token)
+        .json(&serde_json::json!({ "name": name, "config_src": "cloudflare" }))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach the Cloudflare API: {}", e))?;
+    let tunnel: TunnelResult = parse_response(response, TUNNEL_PERMISSION).await?;
+
+    // Older API responses don't include the token; it has its own endpoint
+    let token = match tunnel.token {
+        Some(token) if !token.is_empty() => token,
+        _ => fetch_tunnel_token(&client, account_id, &tunnel.id, api_token).await?,
+    };
+
+    Ok(CreatedTunnel {
+        id: tunnel.id,
+        name: tunnel.name,
+        token,
+    })
+}
+
+async fn fetch_tunnel_token(
+    client: &reqwest::Client,
+    account_id: &str,
+    tunnel_id: &str,
+    api_token: &str,
+) -> Result<String, String> {
+    let response = client
+        .get(format!("{}/accounts/{}/cfd_tunnel/{}/token", API_BASE, account_id, tunnel_id))
+        .bearer_auth(api_token)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach the Cloudflare API: {}", e))?;
+    parse_response::<String>(response, TUNNEL_PERMISSION).await
+}
+
+/// An existing tunnel on the account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountTunnel {
+    pub id: String,
+    pub name: String,
+    /// healthy, degraded, down or inactive
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default, alias = "created_at")]
+    pub created_at: Option<String>,
+    /// Ingress is managed in the dashboard rather than a local config file
+    #[serde(default, alias = "remote_config")]
+    pub remote_config: bool,
+}
+
+/// Largest page the tunnels endpoint serves
+const TUNNELS_PER_PAGE: u32 = 100;
+
+/// Every non-deleted tunnel on the account, across all pages
+pub async fn list_account_tunnels(account_id: &str, api_token: &str) -> Result<Vec<AccountTunnel>, String> {
+    let account_id = account_id.trim();
+    let api_token = api_token.trim();
+    if account_id.is_empty() || api_token.is_empty() {
+        return Err("Account ID and API token are required".to_string());
+    }
+
+    let client = client()?;
+    let mut tunnels = Vec::new();
+    let mut page = 1;
+    loop {
+        let response = client
+            .get(format!("{}/accounts/{}/cfd_tunnel", API_BASE, account_id))
+            .query(&[
+                ("is_deleted", "false".to_string()),
+                ("per_page", TUNNELS_PER_PAGE.to_string()),
+                ("page", page.to_string()),
+            ])
+            .bearer_auth(api_token)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach the Cloudflare API: {}", e))?;
+        let (batch, info): (Vec<AccountTunnel>, _) = parse_page(response, TUNNEL_PERMISSION).await?;
+        let short_page = (batch.len() as u32) < TUNNELS_PER_PAGE;
+        tunnels.extend(batch);
+        // Trust total_pages when present; otherwise a short page is the last one
+        let last_page = match info.and_then(|i| i.total_pages) {
+            Some(total) => page >= total,
+            None => short_page,
+        };
+        if last_page {
+            break;
+        }
+        page += 1;
+    }
+    Ok(tunnels)
+}
+
+/// Connector token for an existing tunnel, e.g. one picked from `list_account_tunnels`
+pub async fn get_tunnel_token(account_id: &str, tunnel_id: &str, api_token: &str) -> Result<String, String> {
+    let account_id = account_id.trim();
+    let tunnel_id = tunnel_id.trim();
+    let api_token = api_token.trim();
+    if account_id.is_empty() || tunnel_id.is_empty() || api_token.is_empty() {
+        return Err("Account ID, tunnel ID and API token are required".to_string());
+    }
+    fetch_tunnel_token(&client()?, account_id, tunnel_id, api_token).await
+}
+
+/// What a connector token points at, from `validate_token`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenInfo {
+    pub account_id: String,
+    pub tunnel_id: String,
+    /// Filled in when the tunnel was looked up through the API
+    pub tunnel_name: Option<String>,
+    /// healthy, degraded, down or inactive, from the API lookup
+    pub status: Option<String>,
+    /// The tunnel was confirmed to exist through the API
+    pub verified: bool,
+}
+
+#[derive(Deserialize)]
+struct TunnelDetails {
+    name: String,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    deleted_at: Option<String>,
+}
+
+/// Decode a connector token and, given an API token, confirm its tunnel still exists
+pub async fn validate_token(token: &str, api_token: Option<&str>) -> Result<TokenInfo, String> {
+    let (account_id, tunnel_id) = crate::cloudflared_config::token_target(token)?;
+    let mut info = TokenInfo {
+        account_id,
+        tunnel_id,
+        tunnel_name: None,
+        status: None,
+        verified: false,
+    };
+    let Some(api_token) = api_token.map(str::trim).filter(|t| !t.is_empty()) else {
+        return Ok(info);
+    };
+
+    let response = client()?
+        .get(format!("{}/accounts/{}/cfd_tunnel/{}", API_BASE, info.account_id, info.tunnel_id))
+        .bearer_auth(api_token)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach the Cloudflare API: {}", e))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err("The token decodes, but its tunnel doesn't exist on the account - it may have been deleted".to_string());
+    }
+    let details: TunnelDetails = parse_response(response, TUNNEL_PERMISSION).await?;
+    if details.deleted_at.is_some() {
+        return Err(format!("The token is for tunnel '{}', which has been deleted", details.name));
+    }
+    info.tunnel_name = Some(details.name);
+    info.status = details.status;
+    info.verified = true;
+    Ok(info)
+}
+
+/// Prefix of the error `route_dns` returns when the hostname already has a record
+/// pointing elsewhere; the UI can offer to retry with `overwrite`
+pub const ERR_DNS_RECORD_EXISTS: &str = "DNS_RECORD_EXISTS";
+
+#[derive(Deserialize)]
+struct ZoneResult {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct DnsRecord {
+    id: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    content: String,
+}
+
+/// Find the zone that owns `hostname` by trying each parent domain, most specific first
+async fn find_zone_id(client: &reqwest::Client, api_token: &str, hostname: &str) -> Result<String, String> {
+    let labels: Vec<&str> = hostname.split('.').collect();
+    for start in 0..labels.len().saturating_sub(1) {
+        let candidate = labels[start..].join(".");
+        let response = client
+            .get(format!("{}/zones", API_BASE))
+            .query(&[("name", candidate.as_str())])
+            .bearer_auth(api_token)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach the Cloudflare API: {}", e))?;
+        let zones: Vec<ZoneResult> = parse_response(response, DNS_PERMISSION).await?;
+        if let Some(zone) = zones.into_iter().next() {
+            return Ok(zone.id);
+        }
+    }
+    Err(format!("No Cloudflare zone found for {} - is the domain on this account?", hostname))
+}
+
+/// Point `hostname` at a named tunnel with a proxied CNAME, like
+/// `cloudflared tunnel route dns`. Existing records for the hostname (e.g. an A and an
+/// AAAA) are only replaced, all of them, when `overwrite` is set; otherwise the error
+/// starts with ERR_DNS_RECORD_EXISTS and lists them.
+pub async fn route_dns(tunnel_id: &str, hostname: &str, api_token: &str, overwrite: bool) -> Result<(), String> {
+    let tunnel_id = tunnel_id.trim();
+    let hostname = hostname.trim().trim_end_matches('.').to_lowercase();
+    let api_token = api_token.trim();
+    if tunnel_id.is_empty() || hostname.is_empty() || api_token.is_empty() {
+        return Err("Tunnel ID, hostname and API token are required".to_string());
+    }
+
+    let client = client()?;
+    let zone_id = find_zone_id(&client, api_token, &hostname).await?;
+    let target = format!("{}.cfargotunnel.com", tunnel_id);
+    let record = serde_json::json!({
+        "type": "CNAME",
+        "name": hostname,
+        "content": target,
+        "proxied": true,
+    });
+
+    let response = client
+        .get(format!("{}/zones/{}/dns_records", API_BASE, zone_id))
+        .query(&[("name", hostname.as_str())])
+        .bearer_auth(api_token)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach the Cloudflare API: {}", e))?;
+    let existing: Vec<DnsRecord> = parse_response(response, DNS_PERMISSION).await?;
+
+    if existing.iter().any(|r| r.record_type == "CNAME" && r.content.eq_ignore_ascii_case(&target)) {
+        return Ok(());
+    }
+    if !existing.is_empty() && !overwrite {
+        let records: Vec<String> = existing
+            .iter()
+            .map(|r| format!("{} record pointing to {}", r.record_type, r.content))
+            .collect();
+        return Err(format!("{}: {} already has {}", ERR_DNS_RECORD_EXISTS, hostname, records.join(", ")));
+    }
+
+    // A CNAME can't share its name with any other record, so every one must go. The
+    // first is rewritten in place, after the rest are deleted.
+    for extra in existing.iter().skip(1) {
+        let response = client
+            .delete(format!("{}/zones/{}/dns_records/{}", API_BASE, zone_id, extra.id))
+            .bearer_auth(api_token)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach the Cloudflare API: {}", e))?;
+        parse_response::<serde_json::Value>(response, DNS_PERMISSION).await?;
+    }
+    let request = match existing.first() {
+        Some(current) => client.put(format!("{}/zones/{}/dns_records/{}", API_BASE, zone_id, current.id)),
+        None => client.post(format!("{}/zones/{}/dns_records", API_BASE, zone_id)),
+    };
+
+    let response = request
+        .bearer_auth(api_token)
+        .json(&record)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach the Cloudflare API: {}", e))?;
+    parse_response::<serde_json::Value>(response, DNS_PERMISSION).await?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct AccountResult {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct TunnelConfigurationResult {
+    #[serde(default)]
+    config: Option<TunnelConfiguration>,
+}
+
+#[derive(Deserialize)]
+struct TunnelConfiguration {
+    #[serde(default)]
+    ingress: Vec<IngressEntry>,
+}
+
+#[derive(Deserialize)]
+struct IngressEntry {
+    #[serde(default)]
+    hostname: Option<String>,
+}
+
+/// Public hostnames in a remotely-managed tunnel's ingress rules, in rule order. The
+/// catch-all rule has no hostname and is left out. The tunnel is looked up in each
+/// account the token can see, so no account ID is needed.
+pub async fn get_tunnel_hostnames(tunnel_id: &str, api_token: &str) -> Result<Vec<String>, String> {
+    let tunnel_id = tunnel_id.trim();
+    let api_token = api_token.trim();
+    if tunnel_id.is_empty() || api_token.is_empty() {
+        return Err("Tunnel ID and API token are required".to_string());
+    }
+
+    let client = client()?;
+    let response = client
+        .get(format!("{}/accounts", API_BASE))
+        .bearer_auth(api_token)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach the Cloudflare API: {}", e))?;
+    let accounts: Vec<AccountResult> = parse_response(response, TUNNEL_PERMISSION).await?;
+
+    for account in accounts {
+        let response = client
+            .get(format!("{}/accounts/{}/cfd_tunnel/{}/configurations", API_BASE, account.id, tunnel_id))
+            .bearer_auth(api_token)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach the Cloudflare API: {}", e))?;
+        // The tunnel belongs to some other account the token can see
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            continue;
+        }
+        let result: TunnelConfigurationResult = parse_response(response, TUNNEL_PERMISSION).await?;
+        let ingress = result.config.map(|c| c.ingress).unwrap_or_default();
+        return Ok(ingress
+            .into_iter()
+            .filter_map(|rule| rule.hostname)
+            .filter(|hostname| !hostname.is_empty())
+            .collect());
+    }
+    Err(format!("Tunnel {} not found in any account this token can access", tunnel_id))
+}
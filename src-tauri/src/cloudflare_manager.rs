@@ -1,17 +1,18 @@
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter};
 use tokio::process::Command;
 use tokio::sync::Notify;
+use regex::Regex;
 
-use crate::types::cloudflare::CloudflareConfig;
+use crate::types::cloudflare::{AccessConfig, CloudflareConfig, DefaultTunnelSettings, DnsProxyConfig, TunnelProtocol};
 
 /// Find cloudflared binary path - checks common installation locations
 /// GUI apps on macOS don't inherit terminal PATH, so we check manually
 fn find_cloudflared_path() -> Option<String> {
-    let possible_paths = [
+    let mut possible_paths: Vec<String> = [
         // Direct command (if in PATH)
         "cloudflared",
         // macOS Homebrew (Apple Silicon)
@@ -26,11 +27,28 @@ fn find_cloudflared_path() -> Option<String> {
         // Windows common paths
         "C:\\Program Files\\cloudflared\\cloudflared.exe",
         "C:\\Program Files (x86)\\cloudflared\\cloudflared.exe",
-        // User local bin
-        &format!("{}/.local/bin/cloudflared", std::env::var("HOME").unwrap_or_default()),
+    ]
+    .iter()
+    .map(|p| p.to_string())
+    .collect();
+
+    // User-local installs - HOME isn't set on Windows, so resolve via dirs
+    #[cfg(not(windows))]
+    let user_paths = [dirs::home_dir().map(|h| h.join(".local").join("bin").join("cloudflared"))];
+    #[cfg(windows)]
+    let user_paths = [
+        dirs::home_dir().map(|h| h.join(".cloudflared").join("cloudflared.exe")),
+        // winget puts a shim here for per-user installs
+        dirs::data_local_dir().map(|d| d.join("Microsoft").join("WinGet").join("Links").join("cloudflared.exe")),
     ];
+    possible_paths.extend(
+        user_paths
+            .into_iter()
+            .flatten()
+            .map(|p| p.to_string_lossy().to_string()),
+    );
     
-    for path in possible_paths {
+    for path in possible_paths.iter().map(String::as_str) {
         if path == "cloudflared" {
             // Check if it's in PATH using `which` or `where`
             #[cfg(unix)]
@@ -74,268 +92,4047 @@ fn find_cloudflared_path() -> Option<String> {
     None
 }
 
-#[derive(Clone, serde::Serialize)]
-struct CloudflareStatusUpdate {
-    id: String,
-    status: String,
-    message: Option<String>,
-    url: Option<String>,
+/// Version string reported by `cloudflared --version`
+async fn cloudflared_version(cloudflared_bin: &str) -> Option<String> {
+    let mut cmd = Command::new(cloudflared_bin);
+    cmd.arg("--version");
+    #[cfg(windows)]
+    {
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = cmd.output().await.ok()?;
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if version.is_empty() {
+        None
+    } else {
+        Some(version)
+    }
 }
 
-struct RunningTunnel {
-    notify_stop: Arc<Notify>,
-    #[allow(dead_code)]
-    handle: tauri::async_runtime::JoinHandle<()>,
+/// Release number from `cloudflared --version` output, e.g. (2024, 4, 1). cloudflared
+/// versions are dates, so they compare as tuples.
+fn parse_cloudflared_version(output: &str) -> Option<(u32, u32, u32)> {
+    lazy_static::lazy_static! {
+        static ref CLOUDFLARED_VERSION: Regex = Regex::new(r"(\d{4})\.(\d{1,2})\.(\d+)").unwrap();
+    }
+    let caps = CLOUDFLARED_VERSION.captures(output)?;
+    Some((caps[1].parse().ok()?, caps[2].parse().ok()?, caps[3].parse().ok()?))
 }
 
-pub struct CloudflareManager {
-    tunnels: Arc<Mutex<HashMap<String, RunningTunnel>>>,
+/// The version a spawn is about to run, for noticing an in-place update between
+/// respawns. Bounded so a wedged binary can't hold up the spawn.
+async fn spawn_version(cloudflared_bin: &str) -> Option<String> {
+    let output = tokio::time::timeout(Duration::from_secs(5), cloudflared_version(cloudflared_bin))
+        .await
+        .ok()??;
+    Some(match parse_cloudflared_version(&output) {
+        Some((year, month, patch)) => format!("{}.{}.{}", year, month, patch),
+        None => output.lines().next().unwrap_or_default().to_string(),
+    })
 }
 
-impl CloudflareManager {
-    pub fn new() -> Self {
-        Self {
-            tunnels: Arc::new(Mutex::new(HashMap::new())),
+/// First cloudflared release with --post-quantum
+const MIN_POST_QUANTUM_VERSION: (u32, u32, u32) = (2022, 9, 1);
+
+/// Error for a flag this cloudflared build rejects
+fn unsupported_flag_message(flag: &str) -> String {
+    if flag == "post-quantum" {
+        "This cloudflared version doesn't support --post-quantum - update cloudflared or turn post-quantum off".to_string()
+    } else {
+        format!("This cloudflared version doesn't support --{} - update cloudflared or remove the flag", flag)
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudflareStatusUpdate {
+    pub id: String,
+    /// The tunnel's display name, for consumers that would otherwise only see the id
+    pub name: String,
+    pub status: String,
+    pub message: Option<String>,
+    pub url: Option<String>,
+    /// Seconds until the next reconnect attempt, set on countdown updates
+    pub retry_in_secs: Option<u64>,
+    /// Machine-readable reason on warning/error updates (see the CODE_* constants)
+    pub code: Option<String>,
+    /// Startup progress on connecting/connected updates
+    pub phase: Option<TunnelPhase>,
+    /// Tunnel UUID cloudflared reported, to check the token is for the expected tunnel
+    pub tunnel_id: Option<String>,
+    /// This process's connector UUID, as listed in the dashboard's connectors view
+    pub connector_id: Option<String>,
+    /// Rapid reconnecting/connected flips seen, on "unstable" updates and the update
+    /// that ends an unstable spell
+    pub flap_count: Option<u32>,
+    /// Milliseconds from spawning cloudflared to its first connection, on that
+    /// "connected" update
+    pub connect_ms: Option<u64>,
+}
+
+/// Steps a tunnel goes through on its way up, in order
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum TunnelPhase {
+    LocatingBinary,
+    Spawning,
+    Authenticating,
+    /// Edge connections registered so far out of the expected HA count
+    RegisteringConnections { registered: u32, total: u32 },
+    Ready,
+}
+
+/// cloudflared opens this many edge connections unless told otherwise
+const DEFAULT_HA_CONNECTIONS: u32 = 4;
+
+/// Expected edge connections, honouring a `--ha-connections` passed via extra_args
+fn expected_connections(config: &CloudflareConfig) -> u32 {
+    let mut args = config.extra_args.iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("--ha-connections") {
+            Some("") => args.next().map(String::as_str),
+            Some(rest) => rest.strip_prefix('='),
+            None => continue,
+        };
+        if let Some(n) = value.and_then(|v| v.trim().parse().ok()).filter(|n| *n > 0) {
+            return n;
         }
     }
+    DEFAULT_HA_CONNECTIONS
+}
 
-    pub fn connect(&self, app: AppHandle, config: CloudflareConfig) {
-        let tunnels = self.tunnels.clone();
-        let config_id = config.id.clone();
-        
-        // Remove existing tunnel if any
-        self.disconnect(&config.id);
+/// The `connIndex=N` a registration line refers to
+fn parse_conn_index(line: &str) -> Option<u32> {
+    lazy_static::lazy_static! {
+        static ref CONN_INDEX_REGEX: Regex = Regex::new(r"connIndex=(\d+)").unwrap();
+    }
+    CONN_INDEX_REGEX
+        .captures(line)
+        .and_then(|caps| caps.get(1))
+        .and_then(|n| n.as_str().parse().ok())
+}
 
-        let notify_stop = Arc::new(Notify::new());
-        let notify_clone = notify_stop.clone();
-        let config_clone = config.clone();
-        
-        let emit_status = move |status: &str, msg: Option<String>, url: Option<String>| {
-            let _ = app.emit("cloudflare-status-changed", CloudflareStatusUpdate {
-                id: config_clone.id.clone(),
-                status: status.to_string(),
-                message: msg,
-                url,
+/// Receiver for everything a tunnel reports. The manager never talks to Tauri
+/// directly, so it can run headless or against a recording sink in tests.
+pub trait StatusSink: Send + Sync {
+    fn emit(&self, update: CloudflareStatusUpdate);
+    fn emit_metrics(&self, _metrics: CloudflareMetricsUpdate) {}
+    fn emit_logs(&self, _batch: CloudflareLogBatch) {}
+    fn emit_fleet(&self, _fleet: FleetStatus) {}
+    fn emit_preflight(&self, _preflight: EdgePreflight) {}
+    fn emit_resource(&self, _resource: CloudflareResourceUpdate) {}
+}
+
+/// Result of the optional edge reachability probe run before cloudflared starts
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EdgePreflight {
+    pub id: String,
+    /// "ok", "no_egress" or "udp_blocked"
+    pub result: String,
+    pub message: String,
+}
+
+/// Tunnel counts across the whole manager, sent whenever they change
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FleetStatus {
+    pub total: usize,
+    pub connected: usize,
+    pub error: usize,
+    pub reconnecting: usize,
+}
+
+impl StatusSink for AppHandle {
+    fn emit(&self, update: CloudflareStatusUpdate) {
+        let _ = Emitter::emit(self, "cloudflare-status-changed", update);
+    }
+
+    fn emit_metrics(&self, metrics: CloudflareMetricsUpdate) {
+        let _ = Emitter::emit(self, "cloudflare-metrics", metrics);
+    }
+
+    fn emit_logs(&self, batch: CloudflareLogBatch) {
+        let _ = Emitter::emit(self, "cloudflare-log", batch);
+    }
+
+    fn emit_fleet(&self, fleet: FleetStatus) {
+        let _ = Emitter::emit(self, "cloudflare-fleet-status", fleet);
+    }
+
+    fn emit_preflight(&self, preflight: EdgePreflight) {
+        let _ = Emitter::emit(self, "cloudflare-preflight", preflight);
+    }
+
+    fn emit_resource(&self, resource: CloudflareResourceUpdate) {
+        let _ = Emitter::emit(self, "cloudflare-resource", resource);
+    }
+}
+
+/// Prints one JSON line per event, for `proxypal tunnel run`
+pub struct StdoutSink;
+
+impl StdoutSink {
+    fn print<S: serde::Serialize>(event: &str, payload: &S) {
+        if let Ok(json) = serde_json::to_string(payload) {
+            println!("{} {}", event, json);
+        }
+    }
+}
+
+impl StatusSink for StdoutSink {
+    fn emit(&self, update: CloudflareStatusUpdate) {
+        Self::print("cloudflare-status-changed", &update);
+    }
+
+    fn emit_metrics(&self, metrics: CloudflareMetricsUpdate) {
+        Self::print("cloudflare-metrics", &metrics);
+    }
+
+    fn emit_logs(&self, batch: CloudflareLogBatch) {
+        Self::print("cloudflare-log", &batch);
+    }
+
+    fn emit_fleet(&self, fleet: FleetStatus) {
+        Self::print("cloudflare-fleet-status", &fleet);
+    }
+
+    fn emit_preflight(&self, preflight: EdgePreflight) {
+        Self::print("cloudflare-preflight", &preflight);
+    }
+
+    fn emit_resource(&self, resource: CloudflareResourceUpdate) {
+        Self::print("cloudflare-resource", &resource);
+    }
+}
+
+// Codes attached to warning/error status updates so the UI can map them to
+// actionable messages instead of echoing cloudflared's text
+const CODE_BINARY_NOT_FOUND: &str = "BINARY_NOT_FOUND";
+const CODE_INVALID_TOKEN: &str = "INVALID_TOKEN";
+const CODE_TOKEN_EXPIRED: &str = "TOKEN_EXPIRED";
+const CODE_PORT_REFUSED: &str = "PORT_REFUSED";
+const CODE_PORT_IN_USE: &str = "PORT_IN_USE";
+const CODE_EDGE_UNREACHABLE: &str = "EDGE_UNREACHABLE";
+const CODE_TUNNEL_IN_USE: &str = "TUNNEL_IN_USE";
+const CODE_SPAWN_FAILED: &str = "SPAWN_FAILED";
+const CODE_RETRIES_EXHAUSTED: &str = "RETRIES_EXHAUSTED";
+const CODE_CLOUDFLARED_ERROR: &str = "CLOUDFLARED_ERROR";
+const CODE_SCHEDULED_START: &str = "SCHEDULED_START";
+const CODE_SCHEDULED_STOP: &str = "SCHEDULED_STOP";
+const CODE_INVALID_CONFIG: &str = "INVALID_CONFIG";
+const CODE_TIMEOUT: &str = "TIMEOUT";
+const CODE_SOCKET_NOT_FOUND: &str = "SOCKET_NOT_FOUND";
+const CODE_EDGE_RECONNECTING: &str = "EDGE_RECONNECTING";
+const CODE_METRICS_EXPOSED: &str = "METRICS_EXPOSED";
+const CODE_UNSUPPORTED_FLAG: &str = "UNSUPPORTED_FLAG";
+const CODE_CONNECT_TIMEOUT: &str = "CONNECT_TIMEOUT";
+const CODE_TLS_VERIFY_DISABLED: &str = "TLS_VERIFY_DISABLED";
+const CODE_DEPRECATED: &str = "DEPRECATED";
+const CODE_VERSION_CHANGED: &str = "VERSION_CHANGED";
+
+fn code_exited(exit_code: i32) -> String {
+    format!("EXITED({})", exit_code)
+}
+
+fn code_signaled(signal: i32) -> String {
+    format!("SIGNALED({})", signal)
+}
+
+/// Names for the signals that usually end a cloudflared process
+fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        6 => "SIGABRT",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        15 => "SIGTERM",
+        _ => return None,
+    })
+}
+
+fn describe_signal(signal: i32) -> String {
+    let name = signal_name(signal).map(|n| format!("{}, ", n)).unwrap_or_default();
+    let hint = match signal {
+        // The kernel's OOM killer is the usual source of an unexpected SIGKILL
+        9 => " - often the system running out of memory",
+        _ => "",
+    };
+    format!("cloudflared was killed by a signal ({}signal {}){}", name, signal, hint)
+}
+
+/// What a non-zero cloudflared exit code means. cloudflared itself exits 1 on any fatal
+/// error (the log lines before it say which), and Go exits 2 on a panic.
+fn describe_exit_code(code: i32) -> String {
+    match code {
+        1 => "cloudflared exited: fatal error, see the log above (code 1)".to_string(),
+        2 => "cloudflared exited: it crashed (code 2)".to_string(),
+        // Launched through a shell or wrapper script that reports signals as 128+n
+        129..=159 => describe_signal(code - 128),
+        // Windows NTSTATUS values, which show up as negative i32s
+        -1073741510 => "cloudflared exited: the console was closed (code 0xC000013A)".to_string(),
+        -1073741819 => "cloudflared exited: it crashed with an access violation (code 0xC0000005)".to_string(),
+        _ => format!("cloudflared exited with unrecognised exit code {}", code),
+    }
+}
+
+/// Machine-readable code and message for a failed exit
+fn exit_failure(status: std::process::ExitStatus) -> (String, String) {
+    if let Some(code) = status.code() {
+        return (code_exited(code), describe_exit_code(code));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return (code_signaled(signal), describe_signal(signal));
+        }
+    }
+    (code_exited(-1), "cloudflared exited without an exit code".to_string())
+}
+
+/// cloudflared couldn't reach Cloudflare's edge (DNS, firewall, UDP blocked...)
+fn is_edge_unreachable(line_lower: &str) -> bool {
+    line_lower.contains("dial to edge")
+        || line_lower.contains("edge discovery")
+        || line_lower.contains("cloudflare edge ips")
+        || line_lower.contains("lookup region")
+}
+
+/// cloudflared failed while talking to an outbound proxy
+fn is_proxy_failure(line_lower: &str) -> bool {
+    line_lower.contains("proxyconnect") || line_lower.contains("proxy error")
+}
+
+/// cloudflared rejected the token itself
+fn is_invalid_token(line_lower: &str) -> bool {
+    line_lower.contains("token is not valid") || line_lower.contains("invalid tunnel token")
+}
+
+/// Another connector is already serving this tunnel from the same connection slot,
+/// e.g. the same token running in a second copy of the app
+fn is_tunnel_in_use(line_lower: &str) -> bool {
+    line_lower.contains("dupconnregistertunnelerror")
+        || line_lower.contains("already connected to this server")
+        || line_lower.contains("duplicate connection")
+        || line_lower.contains("tunnel is already running")
+}
+
+/// The edge refused the tunnel's credentials (revoked, deleted or expired token).
+/// Returns the code to report, or None if the line isn't an auth failure.
+fn auth_failure_code(line_lower: &str) -> Option<&'static str> {
+    let rejected = is_invalid_token(line_lower)
+        || line_lower.contains("unauthorized")
+        || line_lower.contains("authentication failed")
+        || line_lower.contains("failed to authenticate")
+        || line_lower.contains("invalid credentials");
+    let expired = line_lower.contains("expired")
+        && (rejected || line_lower.contains("token") || line_lower.contains("credential"));
+    if expired {
+        Some(CODE_TOKEN_EXPIRED)
+    } else if rejected {
+        Some(CODE_INVALID_TOKEN)
+    } else {
+        None
+    }
+}
+
+const TUNNEL_IN_USE_MESSAGE: &str = "This tunnel is already running elsewhere (another copy of the app or another machine using the same token). Stop the other connector or use a separate tunnel.";
+
+fn auth_failure_message(code: &str) -> &'static str {
+    if code == CODE_TOKEN_EXPIRED {
+        "Tunnel token has expired - copy a fresh token from the Cloudflare dashboard"
+    } else {
+        "Cloudflare rejected the tunnel token - it may have been revoked or the tunnel deleted. Copy a fresh token from the dashboard"
+    }
+}
+
+/// Delay between cloudflared respawn attempts
+const RETRY_DELAY_SECS: u64 = 5;
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudflareMetricsUpdate {
+    pub id: String,
+    pub total_requests: f64,
+    pub request_errors: f64,
+    pub concurrent_requests: f64,
+    pub ha_connections: f64,
+    pub bytes_sent: f64,
+    pub bytes_received: f64,
+}
+
+/// CPU and memory of a tunnel's cloudflared process, sampled with the metrics
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudflareResourceUpdate {
+    pub id: String,
+    /// Share of one core since the previous sample, so a busy multi-threaded process
+    /// can read over 100 like in top
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+}
+
+/// How often cloudflared's metrics endpoint is scraped
+const METRICS_SCRAPE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Parse cloudflared's Prometheus text exposition into the counters the UI cares about.
+/// Series with labels (e.g. per connIndex) are summed.
+fn parse_metrics(id: &str, body: &str) -> CloudflareMetricsUpdate {
+    let mut update = CloudflareMetricsUpdate {
+        id: id.to_string(),
+        ..Default::default()
+    };
+
+    for line in body.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let (Some(series), Some(value)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let Ok(value) = value.parse::<f64>() else {
+            continue;
+        };
+        let name = series.split('{').next().unwrap_or(series);
+
+        match name {
+            "cloudflared_tunnel_total_requests" => update.total_requests += value,
+            "cloudflared_tunnel_request_errors" => update.request_errors += value,
+            "cloudflared_tunnel_concurrent_requests_per_tunnel" => update.concurrent_requests += value,
+            "cloudflared_tunnel_ha_connections" => update.ha_connections += value,
+            "quic_client_sent_bytes" => update.bytes_sent += value,
+            "quic_client_receive_bytes" => update.bytes_received += value,
+            _ => {}
+        }
+    }
+
+    update
+}
+
+/// Record a scrape's request count and stop the tunnel if it's a quick tunnel that
+/// has served nothing for its idle timeout. Its task then reports `idle_disconnect`.
+fn check_idle(tunnels: &Mutex<HashMap<String, RunningTunnel>>, id: &str, total_requests: f64) {
+    let mut tunnels = lock_recover(tunnels);
+    let Some(tunnel) = tunnels.get(id) else {
+        return;
+    };
+    let Some(timeout) = tunnel.config.idle_timeout_secs.filter(|_| tunnel.config.tunnel_token.is_empty()) else {
+        return;
+    };
+    let idle = {
+        let mut runtime = lock_recover(&tunnel.runtime);
+        let now = Instant::now();
+        // Any change counts as traffic, including the reset when cloudflared respawns
+        if !runtime.last_traffic.is_some_and(|(seen, _)| seen == total_requests) {
+            runtime.last_traffic = Some((total_requests, now));
+        }
+        let idle = runtime
+            .last_traffic
+            .is_some_and(|(_, since)| now.duration_since(since) >= Duration::from_secs(timeout));
+        runtime.idle_stopped = idle;
+        idle
+    };
+    if !idle {
+        return;
+    }
+    let removed = tunnels.remove(id);
+    drop(tunnels);
+    if let Some(tunnel) = removed {
+        tunnel.notify_stop.notify_one();
+        #[cfg(debug_assertions)]
+        println!("[Cloudflare Manager] Idle timeout, stopping tunnel: {} ({})", tunnel.config.name, id);
+    }
+}
+
+/// Fetch the raw metrics page from cloudflared's --metrics server
+async fn scrape_metrics(client: &reqwest::Client, addr: &str) -> Result<String, String> {
+    // A wildcard bind is listening on loopback too, which is where to scrape it
+    let addr = if let Some(port) = addr.strip_prefix("0.0.0.0:").or_else(|| addr.strip_prefix(':')) {
+        format!("127.0.0.1:{}", port)
+    } else if let Some(port) = addr.strip_prefix("[::]:") {
+        format!("[::1]:{}", port)
+    } else {
+        addr.to_string()
+    };
+    let url = if addr.starts_with("http://") || addr.starts_with("https://") {
+        format!("{}/metrics", addr.trim_end_matches('/'))
+    } else {
+        format!("http://{}/metrics", addr)
+    };
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("Metrics endpoint returned {}", response.status()));
+    }
+    response.text().await.map_err(|e| e.to_string())
+}
+
+/// Mask the tunnel token, and anything shaped like one, before a line is logged,
+/// buffered or sent to the frontend. Tokens are long-lived credentials.
+fn redact_secrets(text: &str, token: &str) -> String {
+    lazy_static::lazy_static! {
+        // Tunnel tokens are base64-encoded JSON, so they start with "eyJ" ({")
+        static ref TOKEN_SHAPED_REGEX: Regex = Regex::new(
+            r"eyJ[A-Za-z0-9+/_=-]{20,}"
+        ).unwrap();
+        static ref TOKEN_FLAG_REGEX: Regex = Regex::new(
+            r"(--token[=\s]+)\S+"
+        ).unwrap();
+    }
+    let mut redacted = if token.is_empty() {
+        text.to_string()
+    } else {
+        text.replace(token, "***")
+    };
+    redacted = TOKEN_SHAPED_REGEX.replace_all(&redacted, "***").into_owned();
+    TOKEN_FLAG_REGEX.replace_all(&redacted, "${1}***").into_owned()
+}
+
+/// cloudflared's log level, from the short prefix it writes after the timestamp
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+/// Read the level prefix from a line like `2024-05-01T10:00:00Z ERR Failed to ...`.
+/// Returns None for unstructured output (banners, help text).
+fn parse_log_level(line: &str) -> Option<LogLevel> {
+    line.split_whitespace().take(2).find_map(|token| match token {
+        "DBG" => Some(LogLevel::Debug),
+        "INF" => Some(LogLevel::Info),
+        "WRN" => Some(LogLevel::Warn),
+        "ERR" => Some(LogLevel::Error),
+        "FTL" => Some(LogLevel::Fatal),
+        _ => None,
+    })
+}
+
+/// Which buffered lines `get_logs` returns. Lines without a level prefix or a
+/// connIndex don't match a filter on that field.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogFilter {
+    pub min_level: Option<LogLevel>,
+    pub conn_index: Option<u32>,
+}
+
+impl LogFilter {
+    fn matches(&self, line: &str) -> bool {
+        if let Some(min_level) = self.min_level {
+            if !parse_log_level(line).is_some_and(|level| level >= min_level) {
+                return false;
+            }
+        }
+        match self.conn_index {
+            Some(conn) => parse_conn_index(line) == Some(conn),
+            None => true,
+        }
+    }
+}
+
+/// Extract a quick-tunnel URL from a log line. cloudflared prints it inside an ASCII
+/// box, so the match stops at the hostname rather than at the next whitespace.
+fn extract_tunnel_url(line: &str) -> Option<String> {
+    lazy_static::lazy_static! {
+        static ref TUNNEL_URL_REGEX: Regex = Regex::new(
+            r"(?i)https://[a-z0-9-]+(?:\.[a-z0-9-]+)*\.(?:trycloudflare\.com|cfargotunnel\.com)"
+        ).unwrap();
+    }
+    TUNNEL_URL_REGEX
+        .find(line)
+        .map(|m| m.as_str().to_lowercase())
+}
+
+/// Public URL of a named tunnel from the ingress config cloudflared logs when the
+/// dashboard pushes it (`INF Updated to new configuration config="{\"ingress\":[...`).
+/// Takes the first concrete hostname; wildcards aren't clickable.
+fn extract_ingress_url(line: &str) -> Option<String> {
+    lazy_static::lazy_static! {
+        static ref INGRESS_HOSTNAME_REGEX: Regex = Regex::new(
+            r#"\\?"hostname\\?"\s*:\s*\\?"([^"\\]+)"#
+        ).unwrap();
+    }
+    if !line.contains("Updated to new configuration") {
+        return None;
+    }
+    INGRESS_HOSTNAME_REGEX
+        .captures_iter(line)
+        .filter_map(|caps| caps.get(1))
+        .map(|m| m.as_str())
+        .find(|hostname| !hostname.contains('*'))
+        .map(|hostname| format!("https://{}", hostname.to_lowercase()))
+}
+
+/// Extract the origin port from cloudflared's per-request dial failures, e.g.
+/// `error="dial tcp 127.0.0.1:3000: connect: connection refused"`
+fn parse_origin_refused(line: &str) -> Option<u16> {
+    lazy_static::lazy_static! {
+        static ref ORIGIN_REFUSED_REGEX: Regex = Regex::new(
+            r"dial tcp \[?[^\s\]]*\]?:(\d+): connect: connection refused"
+        ).unwrap();
+    }
+    ORIGIN_REFUSED_REGEX
+        .captures(line)
+        .and_then(|caps| caps.get(1))
+        .and_then(|port| port.as_str().parse().ok())
+}
+
+/// IDs cloudflared logs at startup: `INF Starting tunnel tunnelID=<uuid>` and
+/// `INF Generated Connector ID: <uuid>`
+fn parse_tunnel_identity(line: &str) -> Option<StatusEvent> {
+    lazy_static::lazy_static! {
+        static ref TUNNEL_ID_REGEX: Regex = Regex::new(
+            r"tunnelID=([0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12})"
+        ).unwrap();
+        static ref CONNECTOR_ID_REGEX: Regex = Regex::new(
+            r"(?i)connector id:\s*([0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12})"
+        ).unwrap();
+    }
+    if let Some(id) = CONNECTOR_ID_REGEX.captures(line).and_then(|caps| caps.get(1)) {
+        return Some(StatusEvent::ConnectorId(id.as_str().to_lowercase()));
+    }
+    TUNNEL_ID_REGEX
+        .captures(line)
+        .and_then(|caps| caps.get(1))
+        .map(|id| StatusEvent::TunnelId(id.as_str().to_lowercase()))
+}
+
+/// cloudflared's own backoff before re-dialing a dropped edge connection, e.g.
+/// `INF Retrying connection in up to 4s connIndex=0`
+fn parse_edge_retry(line_lower: &str) -> Option<Option<u64>> {
+    lazy_static::lazy_static! {
+        static ref EDGE_RETRY_REGEX: Regex = Regex::new(r"retrying connection in up to (\d+)s").unwrap();
+    }
+    if !line_lower.contains("retrying connection") {
+        return None;
+    }
+    Some(
+        EDGE_RETRY_REGEX
+            .captures(line_lower)
+            .and_then(|caps| caps.get(1))
+            .and_then(|secs| secs.as_str().parse().ok()),
+    )
+}
+
+/// The message of a WRN line about a deprecated flag or setting, without the timestamp
+/// and level, e.g. `WRN The --region flag is deprecated ...`
+fn parse_deprecation(line: &str, line_lower: &str) -> Option<String> {
+    if !(line_lower.contains("deprecat")
+        || line_lower.contains("no longer supported")
+        || line_lower.contains("will be removed")) {
+        return None;
+    }
+    let (_, message) = line.split_once(" WRN ")?;
+    Some(message.trim().to_string())
+}
+
+/// What a single cloudflared output line tells us about the tunnel
+#[derive(Debug, Clone, PartialEq)]
+enum StatusEvent {
+    /// An edge connection registered ("Registered tunnel connection connIndex=0 ...")
+    Registered,
+    /// An edge connection dropped; cloudflared re-dials it on its own, the process stays up
+    EdgeDisconnected,
+    /// cloudflared is backing off before re-dialing, for up to this many seconds
+    EdgeRetry(Option<u64>),
+    /// The tunnel's UUID, from the startup lines
+    TunnelId(String),
+    /// This process's connector UUID
+    ConnectorId(String),
+    /// Quick-tunnel URL from the startup banner, or a named tunnel's first ingress hostname
+    TunnelUrl(String),
+    /// The tunnel is up but a request to the local origin was refused
+    OriginRefused(u16),
+    /// ERR/FTL while talking to the outbound proxy
+    ProxyFailure,
+    /// The edge rejected the token; retrying with the same token can't succeed
+    AuthFailed { code: &'static str },
+    /// WRN/ERR/FTL saying another connector already holds this tunnel's connection
+    TunnelInUse,
+    /// ERR/FTL that only says work was cancelled ("context canceled"), which cloudflared
+    /// logs while it's being shut down. Reported as an Error unless we're stopping it.
+    Canceled { fatal: bool },
+    /// Any other ERR/FTL line; only `fatal` means cloudflared is going down
+    Error { fatal: bool, code: &'static str },
+    /// cloudflared rejected a command-line flag it doesn't know (too old for it)
+    UnsupportedFlag(String),
+    /// WRN about a deprecated flag or setting; the tunnel still works
+    Deprecated(String),
+    /// Protocol negotiated or connection established without a registration line
+    Connected,
+}
+
+/// Classify one (already redacted) output line. ERR/FTL are taken from cloudflared's
+/// own level prefix, so hostnames or config dumps that merely contain "error" or
+/// "failed" aren't flagged.
+fn classify_line(line: &str) -> Option<StatusEvent> {
+    let line_lower = line.to_lowercase();
+    let level = parse_log_level(line);
+
+    lazy_static::lazy_static! {
+        static ref UNDEFINED_FLAG: Regex = Regex::new(r"flag provided but not defined: -{1,2}([A-Za-z0-9-]+)").unwrap();
+    }
+    // Printed by the flag parser without a level prefix, before cloudflared exits
+    if let Some(caps) = UNDEFINED_FLAG.captures(line) {
+        return Some(StatusEvent::UnsupportedFlag(caps[1].to_string()));
+    }
+    // Deprecation notices often mention connections or errors, so they go before the
+    // checks those words would trip
+    if level == Some(LogLevel::Warn) {
+        if let Some(message) = parse_deprecation(line, &line_lower) {
+            return Some(StatusEvent::Deprecated(message));
+        }
+    }
+    // cloudflared's internal edge reconnects. "Unregistered" also contains "registered",
+    // so these come first.
+    if line_lower.contains("unregistered tunnel connection")
+        || (line_lower.contains("connection terminated") && line_lower.contains("connindex")) {
+        return Some(StatusEvent::EdgeDisconnected);
+    }
+    if let Some(retry_in_secs) = parse_edge_retry(&line_lower) {
+        return Some(StatusEvent::EdgeRetry(retry_in_secs));
+    }
+    // cloudflared logs these on success:
+    // "INF Connection ... registered connIndex=..."
+    // "INF Registered tunnel connection connIndex=..."
+    if line_lower.contains("registered")
+        && (line_lower.contains("connection") || line_lower.contains("connindex")) {
+        return Some(StatusEvent::Registered);
+    }
+    if let Some(url) = extract_tunnel_url(line).or_else(|| extract_ingress_url(line)) {
+        return Some(StatusEvent::TunnelUrl(url));
+    }
+    let is_error = matches!(level, Some(LogLevel::Error | LogLevel::Fatal));
+    // A refused proxy also reads "dial tcp ...: connection refused", so check it first
+    if is_error && is_proxy_failure(&line_lower) {
+        return Some(StatusEvent::ProxyFailure);
+    }
+    if let Some(port) = parse_origin_refused(line) {
+        return Some(StatusEvent::OriginRefused(port));
+    }
+    if (is_error || level == Some(LogLevel::Warn)) && is_tunnel_in_use(&line_lower) {
+        return Some(StatusEvent::TunnelInUse);
+    }
+    if is_error {
+        if let Some(code) = auth_failure_code(&line_lower) {
+            return Some(StatusEvent::AuthFailed { code });
+        }
+        if line_lower.contains("context canceled") {
+            return Some(StatusEvent::Canceled { fatal: level == Some(LogLevel::Fatal) });
+        }
+        let code = if is_edge_unreachable(&line_lower) {
+            CODE_EDGE_UNREACHABLE
+        } else {
+            CODE_CLOUDFLARED_ERROR
+        };
+        return Some(StatusEvent::Error { fatal: level == Some(LogLevel::Fatal), code });
+    }
+    if let Some(identity) = parse_tunnel_identity(line) {
+        return Some(identity);
+    }
+    // `access tcp` and `proxy-dns` have no edge registration; they're ready once the
+    // local listener is up
+    if line_lower.contains("initial protocol")
+        || line_lower.contains("connection established")
+        || line_lower.contains("start websocket listener")
+        || line_lower.contains("starting dns over https proxy server") {
+        return Some(StatusEvent::Connected);
+    }
+    None
+}
+
+/// Forward each line of a child pipe into the shared output channel
+fn spawn_line_pump<R>(stream: R, tx: tokio::sync::mpsc::UnboundedSender<String>)
+where
+    R: tokio::io::AsyncRead + Unpin + Send + 'static,
+{
+    tauri::async_runtime::spawn(async move {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let mut lines = BufReader::new(stream).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Check whether anything accepts TCP connections on the local origin
+async fn is_port_listening(host: &str, port: u16) -> bool {
+    matches!(
+        tokio::time::timeout(
+            Duration::from_millis(500),
+            tokio::net::TcpStream::connect((host, port)),
+        )
+        .await,
+        Ok(Ok(_))
+    )
+}
+
+/// Edge hostname cloudflared dials; both QUIC (UDP) and http2 (TCP) use port 7844
+const EDGE_PROBE_HOST: &str = "region1.v2.argotunnel.com";
+const EDGE_PROBE_PORT: u16 = 7844;
+const EDGE_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Whether the edge answers QUIC over UDP. A long-header packet with a version no
+/// server speaks must be answered with Version Negotiation (RFC 9000 6.1), so any
+/// reply means UDP gets through.
+async fn probe_edge_udp(addr: std::net::SocketAddr) -> bool {
+    let bind: std::net::SocketAddr = if addr.is_ipv4() {
+        (std::net::Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let Ok(socket) = tokio::net::UdpSocket::bind(bind).await else {
+        return false;
+    };
+    // Servers ignore Initials under 1200 bytes, so pad
+    let mut packet = vec![0u8; 1200];
+    packet[0] = 0xc0;
+    packet[1..5].copy_from_slice(&[0x1a, 0x2a, 0x3a, 0x4a]);
+    packet[5] = 8;
+    packet[6..14].copy_from_slice(b"proxypal");
+    packet[14] = 0;
+    if socket.send_to(&packet, addr).await.is_err() {
+        return false;
+    }
+    let mut reply = [0u8; 1500];
+    matches!(tokio::time::timeout(EDGE_PROBE_TIMEOUT, socket.recv_from(&mut reply)).await, Ok(Ok(_)))
+}
+
+/// Check the machine can reach Cloudflare's edge at all, and over which transport
+async fn edge_preflight(id: &str) -> EdgePreflight {
+    let result = |result: &str, message: &str| EdgePreflight {
+        id: id.to_string(),
+        result: result.to_string(),
+        message: message.to_string(),
+    };
+    let addr = match tokio::time::timeout(EDGE_PROBE_TIMEOUT, tokio::net::lookup_host((EDGE_PROBE_HOST, EDGE_PROBE_PORT))).await {
+        Ok(Ok(mut addrs)) => addrs.next(),
+        _ => None,
+    };
+    let Some(addr) = addr else {
+        return result("no_egress", "Can't resolve Cloudflare's edge - check DNS and your internet connection");
+    };
+    let (tcp_ok, udp_ok) = tokio::join!(
+        async { matches!(tokio::time::timeout(EDGE_PROBE_TIMEOUT, tokio::net::TcpStream::connect(addr)).await, Ok(Ok(_))) },
+        probe_edge_udp(addr),
+    );
+    match (tcp_ok, udp_ok) {
+        (_, true) => result("ok", "Cloudflare's edge is reachable"),
+        (true, false) => result("udp_blocked", "UDP to Cloudflare's edge is blocked, so QUIC will fail - set TUNNEL_TRANSPORT_PROTOCOL=http2 (or add --protocol http2) to use TCP"),
+        (false, false) => result("no_egress", "Can't reach Cloudflare's edge on port 7844 over TCP or UDP - a firewall is likely blocking outbound connections"),
+    }
+}
+
+/// One reading of a process's counters
+#[derive(Debug, Clone, PartialEq)]
+struct ProcessSample {
+    /// Total CPU time used so far, user plus system
+    cpu_secs: f64,
+    rss_bytes: u64,
+    /// When the process started, in whatever form the platform reports it; a PID
+    /// whose start changed belongs to a different process
+    started: String,
+}
+
+/// Parse /proc/<pid>/stat and the VmRSS line of /proc/<pid>/status. The command name
+/// in the stat line can hold spaces and parens, so fields are counted after the last ')'.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_sample(stat: &str, status: &str) -> Option<ProcessSample> {
+    // Kernel clock ticks; USER_HZ is 100 on every architecture Linux ships
+    const TICKS_PER_SEC: f64 = 100.0;
+    let fields: Vec<&str> = stat.get(stat.rfind(')')? + 1..)?.split_whitespace().collect();
+    // Offsets from field 3 (state): utime is field 14, stime 15, starttime 22
+    let utime: f64 = fields.get(11)?.parse().ok()?;
+    let stime: f64 = fields.get(12)?.parse().ok()?;
+    let started = fields.get(19)?.to_string();
+    let rss_kb: u64 = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmRSS:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    Some(ProcessSample {
+        cpu_secs: (utime + stime) / TICKS_PER_SEC,
+        rss_bytes: rss_kb * 1024,
+        started,
+    })
+}
+
+/// ps's cumulative CPU time: [[dd-]hh:]mm:ss[.cc]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_ps_time(value: &str) -> Option<f64> {
+    let (days, rest) = match value.split_once('-') {
+        Some((d, rest)) => (d.parse::<f64>().ok()?, rest),
+        None => (0.0, value),
+    };
+    let mut secs = 0.0;
+    for part in rest.split(':') {
+        secs = secs * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(days * 86_400.0 + secs)
+}
+
+/// Sample the given processes in one go; PIDs that are gone are missing from the result
+async fn sample_processes(pids: &[u32]) -> HashMap<u32, ProcessSample> {
+    let mut samples = HashMap::new();
+    if pids.is_empty() {
+        return samples;
+    }
+    #[cfg(target_os = "linux")]
+    for &pid in pids {
+        let stat = tokio::fs::read_to_string(format!("/proc/{}/stat", pid)).await;
+        let status = tokio::fs::read_to_string(format!("/proc/{}/status", pid)).await;
+        if let (Ok(stat), Ok(status)) = (stat, status) {
+            if let Some(sample) = parse_proc_sample(&stat, &status) {
+                samples.insert(pid, sample);
+            }
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let list = pids.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(",");
+        let Ok(output) = Command::new("ps")
+            .args(["-o", "pid=,rss=,time=,lstart=", "-p", &list])
+            .output()
+            .await
+        else {
+            return samples;
+        };
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let mut cols = line.split_whitespace();
+            let (Some(pid), Some(rss_kb), Some(time)) = (cols.next(), cols.next(), cols.next()) else {
+                continue;
+            };
+            let (Ok(pid), Ok(rss_kb), Some(cpu_secs)) = (pid.parse::<u32>(), rss_kb.parse::<u64>(), parse_ps_time(time)) else {
+                continue;
+            };
+            samples.insert(pid, ProcessSample {
+                cpu_secs,
+                rss_bytes: rss_kb * 1024,
+                started: cols.collect::<Vec<_>>().join(" "),
             });
+        }
+    }
+    #[cfg(windows)]
+    {
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        let list = pids.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(",");
+        let script = format!(
+            "Get-Process -Id {} -ErrorAction SilentlyContinue | ForEach-Object {{ \"$($_.Id) $($_.CPU) $($_.WorkingSet64) $($_.StartTime.Ticks)\" }}",
+            list
+        );
+        let Ok(output) = Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .await
+        else {
+            return samples;
+        };
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            let [pid, cpu, rss, started] = cols[..] else {
+                continue;
+            };
+            let (Ok(pid), Ok(cpu_secs), Ok(rss_bytes)) = (pid.parse::<u32>(), cpu.parse::<f64>(), rss.parse::<u64>()) else {
+                continue;
+            };
+            samples.insert(pid, ProcessSample { cpu_secs, rss_bytes, started: started.to_string() });
+        }
+    }
+    samples
+}
+
+/// The previous reading for a tunnel's process, to turn CPU time into a percentage
+struct ResourceBaseline {
+    pid: u32,
+    sample: ProcessSample,
+    at: Instant,
+}
+
+/// Look up which process is listening on a local TCP port, e.g. "node (pid 4242)"
+async fn find_port_owner(port: u16) -> Option<String> {
+    #[cfg(unix)]
+    {
+        let output = Command::new("lsof")
+            .args(["-nP", &format!("-iTCP:{}", port), "-sTCP:LISTEN", "-Fpc"])
+            .output()
+            .await
+            .ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut pid = None;
+        let mut name = None;
+        for line in stdout.lines() {
+            if let Some(p) = line.strip_prefix('p') {
+                pid.get_or_insert_with(|| p.to_string());
+            } else if let Some(c) = line.strip_prefix('c') {
+                name.get_or_insert_with(|| c.to_string());
+            }
+        }
+        match (name, pid) {
+            (Some(name), Some(pid)) => Some(format!("{} (pid {})", name, pid)),
+            (None, Some(pid)) => Some(format!("pid {}", pid)),
+            _ => None,
+        }
+    }
+    #[cfg(windows)]
+    {
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        let output = Command::new("netstat")
+            .args(["-ano", "-p", "TCP"])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .await
+            .ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let suffix = format!(":{}", port);
+        stdout.lines().find_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() >= 5 && cols[1].ends_with(&suffix) && cols[3] == "LISTENING" {
+                Some(format!("pid {}", cols[4]))
+            } else {
+                None
+            }
+        })
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = port;
+        None
+    }
+}
+
+/// Named tunnels with local ingress rules run from a config file we generate
+fn uses_generated_config(config: &CloudflareConfig) -> bool {
+    config.access.is_none() && !config.tunnel_token.is_empty() && !config.ingress.is_empty()
+}
+
+/// The user's own cloudflared config file, if one is set
+fn external_config_file(config: &CloudflareConfig) -> Option<std::path::PathBuf> {
+    let path = config
+        .config_file
+        .as_deref()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(std::path::PathBuf::from)?;
+    // Resolved the way cloudflared will, so the existence check and watch see the same file
+    match working_dir(config) {
+        Some(dir) if path.is_relative() => Some(dir.join(path)),
+        _ => Some(path),
+    }
+}
+
+/// The directory cloudflared is started in, when one is configured
+fn working_dir(config: &CloudflareConfig) -> Option<std::path::PathBuf> {
+    config
+        .working_dir
+        .as_deref()
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(std::path::PathBuf::from)
+}
+
+/// Spawning in a missing directory fails with a bare "not found", which reads as if
+/// cloudflared itself were missing
+async fn check_working_dir(config: &CloudflareConfig) -> Result<(), String> {
+    let Some(dir) = working_dir(config) else {
+        return Ok(());
+    };
+    match tokio::fs::metadata(&dir).await {
+        Ok(meta) if meta.is_dir() => Ok(()),
+        Ok(_) => Err(format!("Working directory {} is not a directory", dir.display())),
+        Err(_) => Err(format!("Working directory {} doesn't exist", dir.display())),
+    }
+}
+
+/// How often a watched config file is checked for changes
+const CONFIG_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+/// A change must hold this long before restarting, so an editor's burst of writes
+/// (truncate, write, rename) restarts cloudflared once
+const CONFIG_WATCH_DEBOUNCE: Duration = Duration::from_millis(750);
+
+async fn modified_time(path: &std::path::Path) -> Option<std::time::SystemTime> {
+    tokio::fs::metadata(path).await.ok()?.modified().ok()
+}
+
+/// Resolve once `path` has changed since `since` and then stopped changing. A file
+/// that disappears counts as a change only once it's back, since editors that save
+/// by rename briefly leave no file at all.
+async fn config_file_changed(path: &std::path::Path, since: Option<std::time::SystemTime>) {
+    loop {
+        tokio::time::sleep(CONFIG_WATCH_INTERVAL).await;
+        let Some(mut seen) = modified_time(path).await else {
+            continue;
         };
+        if Some(seen) == since {
+            continue;
+        }
+        loop {
+            tokio::time::sleep(CONFIG_WATCH_DEBOUNCE).await;
+            match modified_time(path).await {
+                Some(now) if now == seen => return,
+                Some(now) => seen = now,
+                None => {}
+            }
+        }
+    }
+}
+
+/// Write the generated config for `config` if it needs one, off the async thread
+async fn prepare_generated_config(config: &CloudflareConfig) -> Result<(), String> {
+    if !uses_generated_config(config) {
+        return Ok(());
+    }
+    let config = config.clone();
+    tauri::async_runtime::spawn_blocking(move || crate::cloudflared_config::write_ingress_config(&config))
+        .await
+        .map_err(|e| format!("Failed to write tunnel config: {}", e))?
+        .map(|_| ())
+}
+
+/// The OS proxy (env vars, then WinINET / `scutil --proxy` / gsettings, via the same
+/// lookup the proxy settings page uses), or None when there isn't one. Blocking.
+fn detect_system_proxy() -> Option<String> {
+    crate::commands::proxy::get_system_proxy().ok().flatten().filter(|p| !p.trim().is_empty())
+}
+
+/// Fill `egress_proxy` from the OS settings when the config asks for that and doesn't
+/// name a proxy itself
+async fn apply_system_proxy(config: &mut CloudflareConfig) {
+    let has_proxy = config.egress_proxy.as_deref().is_some_and(|p| !p.trim().is_empty());
+    if !config.use_system_proxy || has_proxy {
+        return;
+    }
+    if let Ok(Some(proxy)) = tauri::async_runtime::spawn_blocking(detect_system_proxy).await {
+        config.egress_proxy = Some(proxy);
+    }
+}
+
+/// Build the cloudflared command line for a tunnel config
+fn build_command(cloudflared_bin: &str, config: &CloudflareConfig) -> Command {
+    // For named tunnels with tokens from Cloudflare Dashboard:
+    // The ingress rules (including URL routing) are configured in the dashboard
+    // So we only need: cloudflared tunnel run --token <token>
+    //
+    // For quick tunnels (no token, just expose a port):
+    // cloudflared tunnel --url http://localhost:<port>
+    let mut cmd = Command::new(cloudflared_bin);
+    if let Some(dir) = working_dir(config) {
+        cmd.current_dir(dir);
+    }
+    if let Some(access) = &config.access {
+        // Access client: forward a local listener to a protected hostname
+        cmd.args(["access", "tcp", "--hostname", access.hostname.trim(), "--url", access.local_bind.trim()]);
+        for (key, value) in &config.env {
+            cmd.env(key, value);
+        }
+        return cmd;
+    }
+    if let Some(dns) = &config.dns_proxy {
+        cmd.args(["proxy-dns", "--address", dns.address.trim(), "--port"]);
+        cmd.arg(dns.port.to_string());
+        for upstream in dns.upstreams.iter().map(|u| u.trim()).filter(|u| !u.is_empty()) {
+            cmd.args(["--upstream", upstream]);
+        }
+        for (key, value) in &config.env {
+            cmd.env(key, value);
+        }
+        return cmd;
+    }
+    cmd.arg("tunnel");
+
+    // Tunnel-level flags must come before the `run` subcommand
+    if config.no_autoupdate {
+        cmd.arg("--no-autoupdate");
+    }
+    if config.post_quantum {
+        cmd.arg("--post-quantum");
+    }
+    if let Some(addr) = config.metrics_addr.as_deref().filter(|a| !a.trim().is_empty()) {
+        cmd.arg("--metrics");
+        cmd.arg(addr.trim());
+    }
+    if let Some(version) = config.edge_ip_version.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+        cmd.arg("--edge-ip-version");
+        cmd.arg(version);
+    }
+    if let Some(grace) = config.grace_period_secs {
+        cmd.arg("--grace-period");
+        cmd.arg(format!("{}s", grace));
+    }
+    if uses_generated_config(config) {
+        cmd.arg("--config");
+        cmd.arg(crate::cloudflared_config::generated_config_path(&config.id));
+    } else if let Some(path) = external_config_file(config) {
+        cmd.arg("--config");
+        cmd.arg(path);
+    }
+
+    if config.tunnel_token.is_empty() {
+        // Quick tunnel mode - expose local port (or socket) directly
+        cmd.arg("--url");
+        cmd.arg(config.origin_url());
+        if let Some(name) = config.origin_server_name.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+            cmd.arg("--origin-server-name");
+            cmd.arg(name);
+        }
+        if let Some(host) = config.http_host_header.as_deref().map(str::trim).filter(|h| !h.is_empty()) {
+            cmd.arg("--http-host-header");
+            cmd.arg(host);
+        }
+        if config.no_tls_verify {
+            cmd.arg("--no-tls-verify");
+        }
+    } else {
+        // Named tunnel mode - use token from dashboard
+        // Ingress rules are configured in Cloudflare Zero Trust dashboard
+        cmd.arg("run");
+        cmd.arg("--token");
+        cmd.arg(&config.tunnel_token);
+    }
+
+    // Power-user flags go last so they apply to the subcommand being run
+    cmd.args(config.extra_args.iter().filter(|a| !a.is_empty()));
+
+    // cloudflared honours the standard proxy variables for its edge connections.
+    // Set before the explicit env so a user-provided HTTPS_PROXY still wins.
+    if let Some(proxy) = config.egress_proxy.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        for key in ["HTTPS_PROXY", "HTTP_PROXY", "ALL_PROXY"] {
+            cmd.env(key, proxy);
+        }
+    }
+
+    for (key, value) in &config.env {
+        if !key.trim().is_empty() {
+            cmd.env(key.trim(), value);
+        }
+    }
+
+    cmd
+}
+
+/// How long orphaned processes get to exit on SIGTERM before they're force-killed
+const ORPHAN_STOP_GRACE: Duration = Duration::from_secs(3);
+
+/// Signal a single process that isn't necessarily a group leader
+async fn signal_process(pid: u32, force: bool) {
+    #[cfg(unix)]
+    {
+        let signal = if force { "-KILL" } else { "-TERM" };
+        let _ = Command::new("kill")
+            .args([signal, &pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .await;
+    }
+    #[cfg(windows)]
+    {
+        // An orphan has no console to receive a polite close, so this is always forced
+        let _ = force;
+        signal_process_group(pid, true).await;
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (pid, force);
+    }
+}
+
+/// Signal cloudflared's whole process group (it runs as the group leader, see
+/// `connect`), so helper processes it spawned go down with it. Returns false when the
+/// graceful request isn't one cloudflared will act on, so the caller needn't wait.
+async fn signal_process_group(pid: u32, force: bool) -> bool {
+    #[cfg(unix)]
+    {
+        let signal = if force { "-KILL" } else { "-TERM" };
+        let _ = Command::new("kill")
+            .args([signal, "--", &format!("-{}", pid)])
+            .status()
+            .await;
+        true
+    }
+    #[cfg(windows)]
+    {
+        // There is no graceful stop on Windows: cloudflared runs with a hidden console
+        // of its own, so neither a console control event nor taskkill's WM_CLOSE reaches
+        // it. The polite taskkill is still sent, then the caller forces straight away.
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        let pid = pid.to_string();
+        let mut args = vec!["/T", "/PID", pid.as_str()];
+        if force {
+            args.insert(0, "/F");
+        }
+        let _ = Command::new("taskkill")
+            .args(&args)
+            .creation_flags(CREATE_NO_WINDOW)
+            .status()
+            .await;
+        force
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (pid, force);
+        false
+    }
+}
+
+/// A cloudflared process found running on the machine
+struct CloudflaredProcess {
+    pid: u32,
+    parent_pid: Option<u32>,
+    /// Name of the parent process, if it's still alive
+    parent_name: Option<String>,
+}
+
+/// Every process named cloudflared, with enough about its parent to tell whose it is
+async fn list_cloudflared_processes() -> Vec<CloudflaredProcess> {
+    #[cfg(unix)]
+    {
+        let Ok(output) = Command::new("ps").args(["-axo", "pid=,ppid=,comm="]).output().await else {
+            return Vec::new();
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut names: HashMap<u32, String> = HashMap::new();
+        let mut processes = Vec::new();
+        for line in stdout.lines() {
+            let mut cols = line.split_whitespace();
+            let (Some(Ok(pid)), Some(Ok(ppid))) = (cols.next().map(str::parse::<u32>), cols.next().map(str::parse::<u32>)) else {
+                continue;
+            };
+            // comm is a full path on macOS and may contain spaces
+            let comm = cols.collect::<Vec<_>>().join(" ");
+            let name = comm.rsplit('/').next().unwrap_or(&comm).to_string();
+            if name == "cloudflared" {
+                processes.push((pid, ppid));
+            }
+            names.insert(pid, name);
+        }
+        processes
+            .into_iter()
+            .map(|(pid, ppid)| CloudflaredProcess {
+                pid,
+                parent_pid: Some(ppid),
+                parent_name: names.get(&ppid).cloned(),
+            })
+            .collect()
+    }
+    #[cfg(windows)]
+    {
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        let script = "Get-CimInstance Win32_Process -Filter \"Name='cloudflared.exe'\" | ForEach-Object { $p = Get-Process -Id $_.ParentProcessId -ErrorAction SilentlyContinue; \"$($_.ProcessId) $($_.ParentProcessId) $($p.ProcessName)\" }";
+        let Ok(output) = Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", script])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .await
+        else {
+            return Vec::new();
+        };
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut cols = line.split_whitespace();
+                let pid = cols.next()?.parse().ok()?;
+                let parent_pid = cols.next().and_then(|p| p.parse().ok());
+                let parent_name = cols.next().map(str::to_string);
+                Some(CloudflaredProcess { pid, parent_pid, parent_name })
+            })
+            .collect()
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        Vec::new()
+    }
+}
+
+async fn process_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .await
+            .is_ok_and(|s| s.success())
+    }
+    #[cfg(windows)]
+    {
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        let Ok(output) = Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .await
+        else {
+            return false;
+        };
+        String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid))
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = pid;
+        false
+    }
+}
+
+/// Outcome of `kill_orphans`
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanSweep {
+    /// Stray cloudflared PIDs found; with `confirm` unset nothing was touched
+    pub pids: Vec<u32>,
+    pub killed: usize,
+}
+
+/// Ask cloudflared to shut down gracefully, force-killing it if it overruns the grace period.
+/// On Unix this is SIGTERM. Windows has no graceful stop (see `signal_process_group`),
+/// so there it's a plain taskkill followed at once by a forced one, with no wait.
+async fn terminate_gracefully(child: &mut tokio::process::Child, grace: Duration) {
+    if let Some(pid) = child.id() {
+        if !grace.is_zero() && signal_process_group(pid, false).await {
+            if tokio::time::timeout(grace, child.wait()).await.is_ok() {
+                // The leader exited; make sure no helper outlived it
+                signal_process_group(pid, true).await;
+                return;
+            }
+            println!("[Cloudflare Manager] cloudflared (pid {}) did not exit within {:?}, killing", pid, grace);
+        }
+        signal_process_group(pid, true).await;
+    }
+    let _ = child.kill().await;
+}
+
+/// Piped output, its own process group, no console window, killed with its handle
+fn configure_process(cmd: &mut Command) {
+    cmd.stdout(std::process::Stdio::piped())
+       .stderr(std::process::Stdio::piped())
+       .stdin(std::process::Stdio::null());
+
+    // Run cloudflared as its own process group leader so stopping the
+    // tunnel can take down any helpers it spawns, not just the direct child
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    #[cfg(windows)]
+    {
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+        cmd.creation_flags(CREATE_NO_WINDOW | CREATE_NEW_PROCESS_GROUP);
+    }
+
+    cmd.kill_on_drop(true);
+}
+
+/// Lock a mutex, recovering the guard if a previous holder panicked. The guarded
+/// maps stay consistent across a panic, so one failure shouldn't brick the manager.
+fn lock_recover<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Path of the persisted {id -> last quick-tunnel URL} map
+fn tunnel_urls_path() -> std::path::PathBuf {
+    crate::config::get_proxypal_config_dir().join("cloudflare_urls.json")
+}
+
+/// Load the last-known quick-tunnel URLs persisted across restarts
+fn load_tunnel_urls() -> HashMap<String, String> {
+    std::fs::read_to_string(tunnel_urls_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Held across each read-modify-write of the JSON state files below, which are updated
+/// from detached blocking tasks that can overlap (e.g. several tunnels at launch)
+static STATE_FILE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Write `value` to `path` through a temp file and rename, so readers never see a
+/// half-written file. Callers hold STATE_FILE_LOCK, which also keeps the temp file
+/// to one writer.
+fn write_json_atomic<T: serde::Serialize>(path: &std::path::Path, value: &T) -> Result<(), String> {
+    let temp_path = path.with_extension("tmp");
+    let data = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    std::fs::write(&temp_path, data).map_err(|e| e.to_string())?;
+    std::fs::rename(&temp_path, path).map_err(|e| e.to_string())
+}
+
+/// Record (or clear) the last-known URL for a tunnel
+fn persist_tunnel_url(id: &str, url: Option<&str>) {
+    let _guard = lock_recover(&STATE_FILE_LOCK);
+    let mut urls = load_tunnel_urls();
+    match url {
+        Some(url) => {
+            urls.insert(id.to_string(), url.to_string());
+        }
+        None => {
+            urls.remove(id);
+        }
+    }
+    if let Err(e) = write_json_atomic(&tunnel_urls_path(), &urls) {
+        eprintln!("[Cloudflare Manager] Failed to persist tunnel URL: {}", e);
+    }
+}
+
+/// Connect times kept per tunnel for the trend view
+const CONNECT_TIMES_KEPT: usize = 10;
+
+fn connect_times_path() -> std::path::PathBuf {
+    crate::config::get_proxypal_config_dir().join("cloudflare_connect_times.json")
+}
+
+/// Recent time-to-first-connection samples per tunnel, oldest first
+fn load_connect_times() -> HashMap<String, Vec<u64>> {
+    std::fs::read_to_string(connect_times_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn record_connect_time(id: &str, connect_ms: u64) {
+    let _guard = lock_recover(&STATE_FILE_LOCK);
+    let mut times = load_connect_times();
+    let samples = times.entry(id.to_string()).or_default();
+    samples.push(connect_ms);
+    let excess = samples.len().saturating_sub(CONNECT_TIMES_KEPT);
+    samples.drain(..excess);
+    if let Err(e) = write_json_atomic(&connect_times_path(), &times) {
+        eprintln!("[Cloudflare Manager] Failed to persist connect time: {}", e);
+    }
+}
+
+/// Rotate a tunnel log once it grows past this size
+const LOG_FILE_MAX_BYTES: u64 = 5 * 1024 * 1024;
+/// Number of rotated files kept besides the active one (<name>.log.1, <name>.log.2)
+const LOG_FILE_BACKUPS: u32 = 2;
+
+/// Path of the active log file for a tunnel
+fn tunnel_log_path(id: &str) -> std::path::PathBuf {
+    let safe_id: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    crate::config::get_proxypal_config_dir()
+        .join("logs")
+        .join(format!("cloudflared-{}.log", safe_id))
+}
+
+/// Shift <name>.log -> <name>.log.1 -> <name>.log.2, dropping the oldest
+fn rotate_log_files(path: &std::path::Path) {
+    let backup = |n: u32| std::path::PathBuf::from(format!("{}.{}", path.display(), n));
+    let _ = std::fs::remove_file(backup(LOG_FILE_BACKUPS));
+    for n in (1..LOG_FILE_BACKUPS).rev() {
+        let _ = std::fs::rename(backup(n), backup(n + 1));
+    }
+    let _ = std::fs::rename(path, backup(1));
+}
+
+/// Start a writer thread that appends cloudflared output to the tunnel's log file.
+/// File IO stays off the async runtime; the thread exits once every sender is dropped.
+fn spawn_log_writer(id: &str) -> Option<std::sync::mpsc::Sender<String>> {
+    use std::io::Write;
+
+    let path = tunnel_log_path(id);
+    if let Some(dir) = path.parent() {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!("[Cloudflare Manager] Failed to create log directory '{}': {}", dir.display(), e);
+            return None;
+        }
+    }
+
+    let open = |path: &std::path::Path| {
+        std::fs::OpenOptions::new().create(true).append(true).open(path)
+    };
+    let mut file = match open(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("[Cloudflare Manager] Failed to open log file '{}': {}", path.display(), e);
+            return None;
+        }
+    };
+    let mut size = file.metadata().map(|m| m.len()).unwrap_or(0);
+
+    let (tx, rx) = std::sync::mpsc::channel::<String>();
+    std::thread::spawn(move || {
+        for line in rx {
+            if size >= LOG_FILE_MAX_BYTES {
+                drop(file);
+                rotate_log_files(&path);
+                file = match open(&path) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        eprintln!("[Cloudflare Manager] Failed to reopen log file '{}': {}", path.display(), e);
+                        return;
+                    }
+                };
+                size = 0;
+            }
+            if writeln!(file, "{}", line).is_ok() {
+                size += line.len() as u64 + 1;
+            }
+        }
+    });
+    Some(tx)
+}
+
+/// Batch window for `cloudflare-log` events
+const LOG_STREAM_INTERVAL: Duration = Duration::from_millis(100);
+/// Lines sent per batch; anything beyond is counted in `dropped` instead
+const LOG_STREAM_MAX_BATCH: usize = 200;
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudflareLogBatch {
+    pub id: String,
+    pub lines: Vec<String>,
+    /// Lines skipped in this window because cloudflared outpaced the batch cap
+    pub dropped: u64,
+}
+
+/// Start a task that batches output lines into one `cloudflare-log` event per window,
+/// so debug-level cloudflared output can't flood the frontend with per-line events.
+/// The task flushes and exits once every sender is dropped.
+fn spawn_log_streamer(sink: Arc<dyn StatusSink>, id: String) -> tokio::sync::mpsc::UnboundedSender<String> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    tauri::async_runtime::spawn(async move {
+        let mut batch: Vec<String> = Vec::new();
+        let mut dropped: u64 = 0;
+        let mut ticker = tokio::time::interval(LOG_STREAM_INTERVAL);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        let mut flush = |batch: &mut Vec<String>, dropped: &mut u64| {
+            if batch.is_empty() && *dropped == 0 {
+                return;
+            }
+            sink.emit_logs(CloudflareLogBatch {
+                id: id.clone(),
+                lines: std::mem::take(batch),
+                dropped: std::mem::take(dropped),
+            });
+        };
+
+        loop {
+            tokio::select! {
+                line = rx.recv() => match line {
+                    Some(line) => {
+                        if batch.len() < LOG_STREAM_MAX_BATCH {
+                            batch.push(line);
+                        } else {
+                            dropped += 1;
+                        }
+                    }
+                    None => {
+                        flush(&mut batch, &mut dropped);
+                        break;
+                    }
+                },
+                _ = ticker.tick() => flush(&mut batch, &mut dropped),
+            }
+        }
+    });
+    tx
+}
+
+/// Fire-and-forget POSTs of status updates to a user webhook. Countdown ticks and
+/// repeats of the status last sent (cloudflared reports "registered" once per edge
+/// connection) are skipped so alerting only sees real transitions.
+#[derive(Clone)]
+struct StatusWebhook {
+    client: reqwest::Client,
+    last_status: Arc<Mutex<Option<String>>>,
+}
+
+impl StatusWebhook {
+    fn new() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .unwrap_or_default(),
+            last_status: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn send(&self, url: &str, update: &CloudflareStatusUpdate) {
+        if update.status == "reconnecting" {
+            return;
+        }
+        {
+            let mut last_status = lock_recover(&self.last_status);
+            if last_status.as_deref() == Some(update.status.as_str()) {
+                return;
+            }
+            *last_status = Some(update.status.clone());
+        }
+        let request = self.client.post(url).json(update);
+        tauri::async_runtime::spawn(async move {
+            if let Err(_e) = request.send().await {
+                #[cfg(debug_assertions)]
+                println!("[cloudflared] Status webhook failed: {}", _e);
+            }
+        });
+    }
+}
+
+/// Flips between reconnecting and connected closer together than this count as flapping
+const FLAP_WINDOW: Duration = Duration::from_secs(2);
+/// Rapid flips in a row before updates are held back
+const FLAP_THRESHOLD: u32 = 2;
+
+/// What to do with a status update on a possibly flapping connection
+#[derive(Debug, PartialEq)]
+enum FlapDecision {
+    Emit,
+    /// Held back; the latest held update goes out once the connection settles
+    Hold,
+    /// Flapping just started: send one "unstable" update in place of this one
+    Unstable(u32),
+}
+
+/// Collapses reconnecting<->connected flapping on a bad network into a single
+/// "unstable" update, so the UI isn't flooded. Slow transitions pass straight through.
+#[derive(Default)]
+struct FlapDebouncer {
+    /// Status of the last flip and when it happened
+    last_flip: Option<(String, Instant)>,
+    flaps: u32,
+    held: Option<CloudflareStatusUpdate>,
+    /// A settle task is already waiting to release `held`
+    settling: bool,
+}
+
+impl FlapDebouncer {
+    fn observe(&mut self, update: &CloudflareStatusUpdate, now: Instant) -> FlapDecision {
+        let status = update.status.as_str();
+        if matches!(status, "warning" | "origin_unreachable") {
+            return FlapDecision::Emit;
+        }
+        if !matches!(status, "reconnecting" | "connected") {
+            // Anything else (error, disconnected...) is a real state change; it
+            // supersedes whatever was held
+            self.last_flip = None;
+            self.flaps = 0;
+            self.held = None;
+            return FlapDecision::Emit;
+        }
+        let flipped = match &self.last_flip {
+            Some((last, at)) if last != status => {
+                if now.duration_since(*at) < FLAP_WINDOW {
+                    self.flaps += 1;
+                } else {
+                    self.flaps = 0;
+                }
+                true
+            }
+            Some(_) => false,
+            None => true,
+        };
+        if flipped {
+            self.last_flip = Some((status.to_string(), now));
+        }
+        if self.flaps < FLAP_THRESHOLD {
+            self.held = None;
+            return FlapDecision::Emit;
+        }
+        let first = self.held.is_none();
+        self.held = Some(update.clone());
+        if first {
+            FlapDecision::Unstable(self.flaps)
+        } else {
+            FlapDecision::Hold
+        }
+    }
+
+    /// The held update, once no flip has happened for FLAP_WINDOW; resets the count
+    fn settle(&mut self, now: Instant) -> Option<CloudflareStatusUpdate> {
+        let (_, at) = self.last_flip.as_ref()?;
+        if now.duration_since(*at) < FLAP_WINDOW {
+            return None;
+        }
+        let mut update = self.held.take()?;
+        update.flap_count = Some(self.flaps);
+        self.flaps = 0;
+        Some(update)
+    }
+}
+
+/// Snapshot of a tunnel's state returned by `get_status`
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudflareTunnelStatus {
+    pub id: String,
+    /// Display name from the running config; None when the tunnel isn't running
+    pub name: Option<String>,
+    /// "inactive" when not running, "paused", or the last status the tunnel emitted
+    pub status: String,
+    /// "quick", "named", "access" or "dns"; None when the tunnel isn't running
+    pub mode: Option<String>,
+    pub local_port: Option<u16>,
+    /// URL detected for the currently running process, if any
+    pub url: Option<String>,
+    /// Last URL detected for this tunnel, persisted across app restarts
+    pub last_known_url: Option<String>,
+    pub tunnel_id: Option<String>,
+    pub connector_id: Option<String>,
+    /// Tags from the running config; empty when the tunnel isn't running
+    pub tags: Vec<String>,
+    /// Health from 1 (flapping, slow, degraded) to 100; 0 when not connected
+    pub quality: u8,
+}
+
+/// Per-tunnel outcome of `connect_many`
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TunnelStartResult {
+    pub id: String,
+    pub name: String,
+    pub accepted: bool,
+    pub error: Option<String>,
+}
+
+/// Outcome of `test_config`
+#[derive(Clone, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TunnelTestResult {
+    pub ok: bool,
+    /// Quick-tunnel URL, when one was assigned
+    pub url: Option<String>,
+    /// Machine-readable failure reason (same codes as status updates)
+    pub code: Option<String>,
+    pub message: Option<String>,
+    /// Non-fatal finding, e.g. nothing listening on the local port yet
+    pub warning: Option<String>,
+}
+
+/// Ports whose usual service shouldn't end up on a public quick-tunnel URL by a typo
+const SENSITIVE_PORTS: &[(u16, &str)] = &[
+    (22, "SSH"),
+    (23, "Telnet"),
+    (135, "Windows RPC"),
+    (139, "NetBIOS"),
+    (445, "SMB file sharing"),
+    (2375, "the Docker API"),
+    (3306, "MySQL"),
+    (3389, "Remote Desktop"),
+    (5432, "PostgreSQL"),
+    (5900, "VNC"),
+    (6379, "Redis"),
+    (9200, "Elasticsearch"),
+    (11211, "Memcached"),
+    (27017, "MongoDB"),
+];
+
+/// A quick tunnel makes its origin public to anyone with the URL, so refuse the
+/// well-known sensitive ports unless `confirm_expose` says it's intended
+fn check_exposure(config: &CloudflareConfig) -> Result<(), String> {
+    let quick = config.tunnel_token.is_empty() && config.access.is_none() && config.dns_proxy.is_none();
+    if !quick || config.confirm_expose || config.origin_protocol == TunnelProtocol::Unix {
+        return Ok(());
+    }
+    match SENSITIVE_PORTS.iter().find(|(port, _)| *port == config.local_port) {
+        Some((port, service)) => Err(format!(
+            "Port {} is usually {}, and a quick tunnel would make it reachable by anyone with the URL. Confirm exposing it if that's intended.",
+            port, service
+        )),
+        None => Ok(()),
+    }
+}
+
+/// How long `test_config` waits for cloudflared to report a connection
+const TEST_CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Dry run: start cloudflared with `config`, wait for it to connect, then tear it
+/// down. Nothing is registered with the manager, so no status events are emitted.
+pub async fn test_config(mut config: CloudflareConfig) -> TunnelTestResult {
+    let fail = |code: &str, message: String| TunnelTestResult {
+        code: Some(code.to_string()),
+        message: Some(message),
+        ..Default::default()
+    };
+
+    config.normalize();
+    if let Err(e) = config.validate().and_then(|_| check_exposure(&config)) {
+        return fail(CODE_INVALID_CONFIG, e);
+    }
+    let Some(cloudflared_bin) = tauri::async_runtime::spawn_blocking(find_cloudflared_path).await.ok().flatten() else {
+        return fail(CODE_BINARY_NOT_FOUND, "cloudflared not found. Please install it first.".to_string());
+    };
+
+    if let Err(e) = prepare_generated_config(&config).await {
+        return fail(CODE_INVALID_CONFIG, e);
+    }
+    if let Err(e) = check_working_dir(&config).await {
+        return fail(CODE_INVALID_CONFIG, e);
+    }
+    apply_system_proxy(&mut config).await;
+
+    let warning = (config.check_local_port && !is_port_listening(&config.origin_host(), config.local_port).await)
+        .then(|| format!("Nothing is listening on port {}", config.local_port));
+
+    let mut cmd = build_command(&cloudflared_bin, &config);
+    configure_process(&mut cmd);
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => return fail(CODE_SPAWN_FAILED, format!("Failed to start: {}", e)),
+    };
+
+    let (line_tx, mut line_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    if let Some(stderr) = child.stderr.take() {
+        spawn_line_pump(stderr, line_tx.clone());
+    }
+    if let Some(stdout) = child.stdout.take() {
+        spawn_line_pump(stdout, line_tx.clone());
+    }
+    drop(line_tx);
+
+    let quick = config.tunnel_token.is_empty() && config.access.is_none() && config.dns_proxy.is_none();
+    let watch = async {
+        let mut url: Option<String> = None;
+        while let Some(raw_line) = line_rx.recv().await {
+            let line = redact_secrets(raw_line.trim_end(), &config.tunnel_token);
+            match classify_line(&line) {
+                Some(StatusEvent::TunnelUrl(found)) => {
+                    url = Some(found);
+                }
+                // A quick tunnel isn't usable until its URL is known
+                Some(StatusEvent::Registered | StatusEvent::Connected) if !quick || url.is_some() => {
+                    return Ok(url);
+                }
+                Some(StatusEvent::AuthFailed { code }) => {
+                    return Err((code, auth_failure_message(code).to_string()));
+                }
+                Some(StatusEvent::TunnelInUse) => {
+                    return Err((CODE_TUNNEL_IN_USE, TUNNEL_IN_USE_MESSAGE.to_string()));
+                }
+                Some(StatusEvent::UnsupportedFlag(flag)) => {
+                    return Err((CODE_UNSUPPORTED_FLAG, unsupported_flag_message(&flag)));
+                }
+                Some(StatusEvent::ProxyFailure) => {
+                    return Err((CODE_EDGE_UNREACHABLE, line.clone()));
+                }
+                Some(StatusEvent::Error { fatal: true, code }) => {
+                    return Err((code, line.clone()));
+                }
+                _ => {}
+            }
+        }
+        Err((CODE_SPAWN_FAILED, "cloudflared exited before connecting".to_string()))
+    };
+    let outcome = tokio::time::timeout(TEST_CONNECT_TIMEOUT, watch).await;
+    terminate_gracefully(&mut child, Duration::ZERO).await;
+
+    match outcome {
+        Ok(Ok(url)) => TunnelTestResult {
+            ok: true,
+            url,
+            warning,
+            ..Default::default()
+        },
+        Ok(Err((code, message))) => TunnelTestResult { warning, ..fail(code, message) },
+        Err(_) => TunnelTestResult {
+            warning,
+            ..fail(CODE_TIMEOUT, format!("No connection within {} seconds", TEST_CONNECT_TIMEOUT.as_secs()))
+        },
+    }
+}
+
+/// One saved tunnel's line in the `test_all` report
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigTestReport {
+    pub id: String,
+    pub name: String,
+    pub ok: bool,
+    pub url: Option<String>,
+    pub code: Option<String>,
+    pub error: Option<String>,
+    pub warning: Option<String>,
+}
+
+/// Most `test_config` runs `test_all` keeps going at once
+const TEST_ALL_CONCURRENCY: usize = 4;
+
+/// How often the scheduler re-reads saved configs and checks tunnel windows
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long `shutdown` waits for tunnels to drain before force-killing them
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of recent cloudflared output lines kept in memory per tunnel
+const LOG_BUFFER_LINES: usize = 500;
+
+/// How far back respawns and edge drops count against a tunnel's quality score
+const QUALITY_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// 0 when down, otherwise 1-100: 15 points off per recent disruption (up to 60), up to
+/// 20 for a slow connect and up to 20 for missing edge connections. Rough, but it only
+/// moves when something happens, so tunnels sort stably by it.
+fn quality_score(connected: bool, recent_disruptions: usize, connect_ms: Option<u64>, up: u32, expected: u32) -> u8 {
+    if !connected {
+        return 0;
+    }
+    let mut score: i64 = 100;
+    score -= (recent_disruptions as i64 * 15).min(60);
+    if let Some(ms) = connect_ms {
+        // Anything under 2s is a normal connect
+        score -= ((ms.saturating_sub(2000) / 500) as i64).min(20);
+    }
+    // Access clients and DNS proxies never register edge connections
+    if up > 0 && expected > up {
+        score -= (20 * (expected - up) / expected) as i64;
+    }
+    score.clamp(1, 100) as u8
+}
+
+/// Mutable per-tunnel state shared between the manager and the tunnel task
+#[derive(Default)]
+struct TunnelRuntime {
+    url: Option<String>,
+    /// Reported by cloudflared at startup; the connector ID changes with every process
+    tunnel_id: Option<String>,
+    connector_id: Option<String>,
+    /// Tail of cloudflared's output, bounded to LOG_BUFFER_LINES
+    logs: VecDeque<String>,
+    /// Stopped via `pause`; the entry stays so `resume` can respawn it
+    paused: bool,
+    /// A cloudflared process is currently alive for this tunnel
+    process_running: bool,
+    /// PID of the live cloudflared process, used to reap it if the task doesn't
+    pid: Option<u32>,
+    /// Number of cloudflared processes spawned; every one after the first is a reconnect
+    spawn_attempts: u64,
+    /// When the current process first reported an edge connection
+    connected_since: Option<Instant>,
+    /// When the current process was spawned; taken by its first "connected" update
+    spawned_at: Option<Instant>,
+    /// Counting down to the next spawn after a failed attempt
+    backing_off: bool,
+    /// Request count from the last metrics scrape, and when it last changed
+    last_traffic: Option<(f64, Instant)>,
+    /// Stopped by the idle timeout rather than by the user
+    idle_stopped: bool,
+    /// Respawns and dropped edge connections, oldest first, within QUALITY_WINDOW
+    disruptions: VecDeque<Instant>,
+    /// How long the current process took to connect
+    connect_ms: Option<u64>,
+    /// Edge connections registered by the current process, out of how many it opens
+    edge_connections: u32,
+    expected_connections: u32,
+    /// Last status sent for this tunnel, ignoring transient warnings; feeds FleetStatus
+    /// and get_status
+    last_status: String,
+    /// The config's `stream_logs` and `status_webhook`, read per line and per update so
+    /// `update_config` can change them without a respawn
+    stream_logs: bool,
+    status_webhook: Option<String>,
+}
+
+impl TunnelRuntime {
+    fn mark_connected(&mut self) {
+        self.connected_since.get_or_insert_with(Instant::now);
+    }
+
+    fn record_disruption(&mut self) {
+        let now = Instant::now();
+        while self.disruptions.front().is_some_and(|t| now.duration_since(*t) > QUALITY_WINDOW) {
+            self.disruptions.pop_front();
+        }
+        self.disruptions.push_back(now);
+    }
+
+    /// Health score for sorting a fleet; see `quality_score`
+    fn quality(&self) -> u8 {
+        let recent = self.disruptions.iter().filter(|t| t.elapsed() <= QUALITY_WINDOW).count();
+        quality_score(
+            self.connected_since.is_some() && !self.paused,
+            recent,
+            self.connect_ms,
+            self.edge_connections,
+            self.expected_connections,
+        )
+    }
+
+    fn push_log(&mut self, line: &str) {
+        if self.logs.len() >= LOG_BUFFER_LINES {
+            self.logs.pop_front();
+        }
+        self.logs.push_back(line.to_string());
+    }
+}
+
+struct RunningTunnel {
+    /// The config this tunnel runs with; edits that don't need a restart, and edits made
+    /// while it's backing off, are stored here
+    config: CloudflareConfig,
+    runtime: Arc<Mutex<TunnelRuntime>>,
+    notify_stop: Arc<Notify>,
+    /// Kill the current cloudflared process and respawn immediately, skipping backoff
+    notify_reconnect: Arc<Notify>,
+    /// Cut a retry delay short so the next spawn uses the config just stored in `config`
+    notify_config: Arc<Notify>,
+    handle: tauri::async_runtime::JoinHandle<()>,
+    /// Which start_tunnel call owns this entry; a task whose entry was replaced by a
+    /// newer one for the same id stays quiet
+    generation: u64,
+}
+
+/// Cheap to clone; clones share the same tunnels and background tasks
+#[derive(Clone)]
+pub struct CloudflareManager {
+    tunnels: Arc<Mutex<HashMap<String, RunningTunnel>>>,
+    /// Shared metrics poller, started with the first tunnel
+    monitor: Arc<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+    scheduler: Arc<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+    metrics_server: Arc<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+    /// Fleet counts last sent, so an event only goes out when they change
+    last_fleet: Arc<Mutex<FleetStatus>>,
+    next_generation: Arc<AtomicU64>,
+    /// Most tunnels allowed to run at once; see `set_max_concurrent`
+    max_concurrent: Arc<AtomicUsize>,
+    /// Merged into each tunnel config by `connect`; see `set_default_settings`
+    defaults: Arc<Mutex<DefaultTunnelSettings>>,
+}
+
+/// Default for `CloudflareManager::set_max_concurrent`
+pub const DEFAULT_MAX_CONCURRENT_TUNNELS: usize = 50;
+
+impl CloudflareManager {
+    pub fn new() -> Self {
+        Self {
+            tunnels: Arc::new(Mutex::new(HashMap::new())),
+            monitor: Arc::new(Mutex::new(None)),
+            scheduler: Arc::new(Mutex::new(None)),
+            metrics_server: Arc::new(Mutex::new(None)),
+            last_fleet: Arc::new(Mutex::new(FleetStatus::default())),
+            next_generation: Arc::new(AtomicU64::new(0)),
+            max_concurrent: Arc::new(AtomicUsize::new(DEFAULT_MAX_CONCURRENT_TUNNELS)),
+            defaults: Arc::new(Mutex::new(DefaultTunnelSettings::default())),
+        }
+    }
+
+    /// Cap how many tunnels may run at once. Each is a cloudflared process plus tasks,
+    /// so a runaway start loop would otherwise exhaust file descriptors and memory.
+    /// Paused tunnels don't count. Lowering the cap doesn't stop tunnels already running.
+    pub fn set_max_concurrent(&self, limit: usize) {
+        self.max_concurrent.store(limit.max(1), Ordering::SeqCst);
+    }
+
+    /// Replace the settings tunnels inherit. Only tunnels started afterwards pick them up.
+    pub fn set_default_settings(&self, defaults: DefaultTunnelSettings) {
+        *lock_recover(&self.defaults) = defaults;
+    }
+
+    fn with_defaults(&self, mut config: CloudflareConfig) -> CloudflareConfig {
+        lock_recover(&self.defaults).apply(&mut config);
+        config
+    }
+
+    /// Serve fleet-wide Prometheus metrics on `bind` (GET /metrics). Meant for a
+    /// loopback address; binding elsewhere exposes tunnel names to the network.
+    pub fn start_metrics_server(&self, bind: String) {
+        let mut server = lock_recover(&self.metrics_server);
+        if server.is_some() {
+            return;
+        }
+        let tunnels = self.tunnels.clone();
+        *server = Some(tauri::async_runtime::spawn(async move {
+            let listener = match tokio::net::TcpListener::bind(&bind).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("[Cloudflare Manager] Failed to bind metrics endpoint on {}: {}", bind, e);
+                    return;
+                }
+            };
+            if listener.local_addr().is_ok_and(|a| !a.ip().is_loopback()) {
+                eprintln!("[Cloudflare Manager] Warning: metrics endpoint {} is reachable from the network", bind);
+            }
+            println!("[Cloudflare Manager] Serving tunnel metrics on http://{}/metrics", bind);
+
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                let tunnels = tunnels.clone();
+                tauri::async_runtime::spawn(async move {
+                    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+                    let mut request = [0u8; 1024];
+                    let read = match tokio::time::timeout(Duration::from_secs(5), stream.read(&mut request)).await {
+                        Ok(Ok(n)) => n,
+                        _ => return,
+                    };
+                    let request_line = String::from_utf8_lossy(&request[..read]);
+                    let is_metrics = request_line.starts_with("GET /metrics ") || request_line.starts_with("GET /metrics?");
+                    let response = if is_metrics {
+                        let body = render_fleet_metrics(&lock_recover(&tunnels));
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    } else {
+                        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                    };
+                    let _ = stream.write_all(response.as_bytes()).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        }));
+    }
+
+    /// Start the background scheduler for tunnels with a `schedule`. Saved configs are
+    /// re-read every check, so edits apply without a restart. The first check brings
+    /// every scheduled tunnel in line with its window, which also covers launching
+    /// mid-window; after that only window transitions act, so a manual stop or start
+    /// is left alone until the next boundary.
+    pub fn start_scheduler(&self, sink: Arc<dyn StatusSink>) {
+        let mut scheduler = lock_recover(&self.scheduler);
+        if scheduler.is_some() {
+            return;
+        }
+        let manager = self.clone();
+        *scheduler = Some(tauri::async_runtime::spawn(async move {
+            let mut last_active: HashMap<String, bool> = HashMap::new();
+            let mut ticker = tokio::time::interval(SCHEDULE_CHECK_INTERVAL);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let configs = tauri::async_runtime::spawn_blocking(|| crate::config::load_config().cloudflare_configs)
+                    .await
+                    .unwrap_or_default();
+                let now = chrono::Local::now().naive_local();
+
+                last_active.retain(|id, _| configs.iter().any(|c| &c.id == id && c.schedule.is_some()));
+                for config in configs {
+                    let Some(schedule) = &config.schedule else {
+                        continue;
+                    };
+                    let active = schedule.is_active_at(now);
+                    if last_active.insert(config.id.clone(), active) == Some(active) {
+                        continue;
+                    }
+
+                    let running = lock_recover(&manager.tunnels).contains_key(&config.id);
+                    let emit_scheduled = |status: &str, code: &str, message: String| {
+                        sink.emit(CloudflareStatusUpdate {
+                            id: config.id.clone(),
+                            name: config.name.clone(),
+                            status: status.to_string(),
+                            message: Some(message),
+                            code: Some(code.to_string()),
+                            ..Default::default()
+                        });
+                    };
+                    if active && !running {
+                        println!("[Cloudflare] Scheduled start: {}", config.name);
+                        emit_scheduled("connecting", CODE_SCHEDULED_START, format!("Starting on schedule ({}-{})", schedule.start, schedule.stop));
+                        if let Err(e) = manager.connect(sink.clone(), config.clone()) {
+                            emit_scheduled("error", CODE_SCHEDULED_START, format!("Scheduled start failed: {}", e));
+                        }
+                    } else if !active && running {
+                        println!("[Cloudflare] Scheduled stop: {}", config.name);
+                        emit_scheduled("disconnecting", CODE_SCHEDULED_STOP, format!("Stopping on schedule ({}-{})", schedule.start, schedule.stop));
+                        manager.disconnect(&config.id);
+                    }
+                }
+            }
+        }));
+    }
+
+    /// Start the shared monitor if it isn't running yet. One task wakes once per
+    /// METRICS_SCRAPE_INTERVAL for all tunnels, instead of one timer per tunnel.
+    fn ensure_monitor(&self, sink: &Arc<dyn StatusSink>) {
+        let mut monitor = lock_recover(&self.monitor);
+        if monitor.is_some() {
+            return;
+        }
+        *monitor = Some(tauri::async_runtime::spawn(monitor_loop(self.tunnels.clone(), sink.clone())));
+    }
+
+    pub fn connect(&self, sink: Arc<dyn StatusSink>, config: CloudflareConfig) -> Result<(), String> {
+        self.start_tunnel(sink, self.with_defaults(config), None)
+    }
+
+    /// `test_config` every config, a few at a time, as each would run with the current
+    /// defaults. Reports come back in the order of `configs`.
+    pub async fn test_all(&self, configs: Vec<CloudflareConfig>) -> Vec<ConfigTestReport> {
+        let mut reports: Vec<ConfigTestReport> = configs
+            .iter()
+            .map(|c| ConfigTestReport {
+                id: c.id.clone(),
+                name: c.name.clone(),
+                ok: false,
+                url: None,
+                code: Some(CODE_SPAWN_FAILED.to_string()),
+                error: Some("The test stopped unexpectedly".to_string()),
+                warning: None,
+            })
+            .collect();
+        let mut pending = configs.into_iter().map(|c| self.with_defaults(c)).enumerate();
+        let mut tests = tokio::task::JoinSet::new();
+        loop {
+            while tests.len() < TEST_ALL_CONCURRENCY {
+                let Some((i, config)) = pending.next() else { break };
+                tests.spawn(async move { (i, test_config(config).await) });
+            }
+            match tests.join_next().await {
+                Some(Ok((i, result))) => {
+                    let report = &mut reports[i];
+                    report.ok = result.ok;
+                    report.url = result.url;
+                    report.code = result.code;
+                    report.error = result.message;
+                    report.warning = result.warning;
+                }
+                // The child is kill_on_drop, so a panicked test still takes its
+                // cloudflared down; its report keeps the placeholder failure
+                Some(Err(_)) => {}
+                None => break,
+            }
+        }
+        reports
+    }
+
+    /// Run a `cloudflared access tcp` client. It shares ids, status events, stop and
+    /// retry handling with tunnels, so `disconnect`/`get_status` work on it too.
+    pub fn connect_access(&self, sink: Arc<dyn StatusSink>, access: AccessConfig) -> Result<(), String> {
+        access.validate()?;
+        self.start_tunnel(sink, access.to_tunnel_config(), None)
+    }
+
+    /// Run a `cloudflared proxy-dns` DNS-over-HTTPS forwarder, managed like an access client
+    pub fn start_dns_proxy(&self, sink: Arc<dyn StatusSink>, dns: DnsProxyConfig) -> Result<(), String> {
+        dns.validate()?;
+        self.start_tunnel(sink, dns.to_tunnel_config(), None)
+    }
+
+    /// Start several tunnels at once (e.g. on app launch). The cloudflared binary is
+    /// resolved once for the whole batch and startups are staggered by `stagger` so
+    /// the edge and the UI don't get a burst of simultaneous connections.
+    pub async fn connect_many(
+        &self,
+        sink: Arc<dyn StatusSink>,
+        configs: Vec<CloudflareConfig>,
+        stagger: Duration,
+    ) -> Vec<TunnelStartResult> {
+        let cloudflared_bin = tauri::async_runtime::spawn_blocking(find_cloudflared_path)
+            .await
+            .ok()
+            .flatten();
+
+        let mut results = Vec::with_capacity(configs.len());
+        for (i, config) in configs.into_iter().enumerate() {
+            let id = config.id.clone();
+            let name = config.name.clone();
+            let result = match &cloudflared_bin {
+                None => Err("cloudflared not found. Please install it first.".to_string()),
+                Some(bin) => {
+                    if i > 0 && !stagger.is_zero() {
+                        tokio::time::sleep(stagger).await;
+                    }
+                    self.start_tunnel(sink.clone(), self.with_defaults(config), Some(bin.clone()))
+                }
+            };
+            results.push(TunnelStartResult {
+                id,
+                name,
+                accepted: result.is_ok(),
+                error: result.err(),
+            });
+        }
+        results
+    }
+
+    fn start_tunnel(&self, sink: Arc<dyn StatusSink>, mut config: CloudflareConfig, cloudflared_bin: Option<String>) -> Result<(), String> {
+        config.normalize();
+        config.validate()?;
+        check_exposure(&config)?;
+
+        let config_id = config.id.clone();
+        let generation = self.next_generation.fetch_add(1, Ordering::SeqCst);
+        self.ensure_monitor(&sink);
+
+        let notify_stop = Arc::new(Notify::new());
+        let notify_clone = notify_stop.clone();
+        let notify_reconnect = Arc::new(Notify::new());
+        let reconnect_clone = notify_reconnect.clone();
+        let notify_config = Arc::new(Notify::new());
+        let config_updated_clone = notify_config.clone();
+        let stored_tunnels = self.tunnels.clone();
+        let config_clone = config.clone();
+        let stored_config = config.clone();
+        let task_sink = sink.clone();
+        let runtime = Arc::new(Mutex::new(TunnelRuntime {
+            stream_logs: config.stream_logs,
+            status_webhook: config.status_webhook.clone(),
+            ..Default::default()
+        }));
+        let runtime_clone = runtime.clone();
+        
+        // Emit a fully-populated update; id and redaction are filled in here. The frontend
+        // replaces its whole status per event, so warnings carry the live URL along.
+        let update_runtime = runtime.clone();
+        let fleet_tunnels = self.tunnels.clone();
+        let last_fleet = self.last_fleet.clone();
+        let webhook = StatusWebhook::new();
+        // Set once a stop is signalled; after that only the stop's own updates go out, so
+        // a late "connected" from the output reader can't follow "disconnected"
+        let stop_requested = Arc::new(AtomicBool::new(false));
+        let update_stop_requested = stop_requested.clone();
+        let deliver = move |mut update: CloudflareStatusUpdate| {
+            if update_stop_requested.load(Ordering::SeqCst)
+                && !matches!(update.status.as_str(), "disconnecting" | "disconnected" | "paused" | "idle_disconnect") {
+                return;
+            }
+            // Replaced by a newer connect for the same id; that task reports from now on.
+            // The name is read from the entry since renaming doesn't restart the tunnel.
+            update.name = match lock_recover(&fleet_tunnels).get(&config_clone.id) {
+                Some(t) if t.generation != generation => return,
+                Some(t) => t.config.name.clone(),
+                None => config_clone.name.clone(),
+            };
+            update.id = config_clone.id.clone();
+            let webhook_url = {
+                let runtime = lock_recover(&update_runtime);
+                if update.url.is_none() && matches!(update.status.as_str(), "warning" | "origin_unreachable") {
+                    update.url = runtime.url.clone();
+                }
+                update.tunnel_id = runtime.tunnel_id.clone();
+                update.connector_id = runtime.connector_id.clone();
+                runtime.status_webhook.clone()
+            };
+            if update.status == "connected" {
+                let spawned_at = lock_recover(&update_runtime).spawned_at.take();
+                if let Some(spawned_at) = spawned_at {
+                    let connect_ms = spawned_at.elapsed().as_millis() as u64;
+                    update.connect_ms = Some(connect_ms);
+                    lock_recover(&update_runtime).connect_ms = Some(connect_ms);
+                    let id = config_clone.id.clone();
+                    tauri::async_runtime::spawn_blocking(move || record_connect_time(&id, connect_ms));
+                }
+            }
+            update.message = update.message.map(|m| redact_secrets(&m, &config_clone.tunnel_token));
+            if let Some(url) = webhook_url.as_deref().map(str::trim).filter(|w| !w.is_empty()) {
+                webhook.send(url, &update);
+            }
+            let transient = matches!(update.status.as_str(), "warning" | "origin_unreachable");
+            if !transient {
+                lock_recover(&update_runtime).last_status = update.status.clone();
+            }
+            sink.emit(update);
+            if !transient {
+                publish_fleet_status(&fleet_tunnels, &last_fleet, sink.as_ref());
+            }
+        };
+        let flaps = Arc::new(Mutex::new(FlapDebouncer::default()));
+        let send_update = move |update: CloudflareStatusUpdate| {
+            let (decision, start_settling) = {
+                let mut flaps = lock_recover(&flaps);
+                let decision = flaps.observe(&update, Instant::now());
+                let start_settling = decision != FlapDecision::Emit && !flaps.settling;
+                if start_settling {
+                    flaps.settling = true;
+                }
+                (decision, start_settling)
+            };
+            match decision {
+                FlapDecision::Emit => deliver(update),
+                FlapDecision::Hold => {}
+                FlapDecision::Unstable(count) => deliver(CloudflareStatusUpdate {
+                    status: "unstable".to_string(),
+                    message: Some("Connection is unstable, cloudflared keeps dropping and reconnecting".to_string()),
+                    url: update.url,
+                    flap_count: Some(count),
+                    ..Default::default()
+                }),
+            }
+            if start_settling {
+                // Release the latest held status once flipping stops
+                let flaps = flaps.clone();
+                let deliver = deliver.clone();
+                tauri::async_runtime::spawn(async move {
+                    loop {
+                        tokio::time::sleep(FLAP_WINDOW).await;
+                        let released = {
+                            let mut state = lock_recover(&flaps);
+                            let released = if state.held.is_none() {
+                                Some(None)
+                            } else {
+                                state.settle(Instant::now()).map(Some)
+                            };
+                            if released.is_some() {
+                                state.settling = false;
+                            }
+                            released
+                        };
+                        match released {
+                            Some(Some(update)) => return deliver(update),
+                            Some(None) => return,
+                            None => {}
+                        }
+                    }
+                });
+            }
+        };
+        let send_update_clone = send_update.clone();
+
+        let emit_status = move |status: &str, msg: Option<String>, url: Option<String>| {
+            send_update(CloudflareStatusUpdate {
+                status: status.to_string(),
+                message: msg,
+                url,
+                ..Default::default()
+            });
+        };
+
+        let emit_status_clone = emit_status.clone();
+
+        // Spawn and register under one lock so overlapping connects for the same id
+        // can't interleave: whichever inserts last owns the entry and stops the one it
+        // replaced, and the new task can't report before its entry exists
+        let mut tunnels = lock_recover(&self.tunnels);
+        // Replacing a tunnel (restart, resume) doesn't add a process
+        let limit = self.max_concurrent.load(Ordering::SeqCst);
+        let running = tunnels
+            .iter()
+            .filter(|(id, t)| **id != config_id && !lock_recover(&t.runtime).paused)
+            .count();
+        if running >= limit {
+            return Err(format!(
+                "Can't run more than {} tunnels at once - stop one first or raise the limit",
+                limit
+            ));
+        }
+        let handle = tauri::async_runtime::spawn(async move {
+            let emit_stopped = {
+                let emit_status = emit_status_clone.clone();
+                let runtime = runtime_clone.clone();
+                let idle_timeout = config.idle_timeout_secs.unwrap_or_default();
+                move || {
+                    let (paused, idle_stopped) = {
+                        let runtime = lock_recover(&runtime);
+                        (runtime.paused, runtime.idle_stopped)
+                    };
+                    if paused {
+                        emit_status("paused", Some("Tunnel paused".into()), None);
+                    } else if idle_stopped {
+                        emit_status("idle_disconnect", Some(format!("No requests for {}s, so the quick tunnel was stopped", idle_timeout)), None);
+                    } else {
+                        emit_status("disconnected", Some("Tunnel stopped".into()), None);
+                    }
+                }
+            };
+
+            let emit_coded = {
+                let send_update = send_update_clone.clone();
+                move |status: &str, code: &str, msg: String| {
+                    send_update(CloudflareStatusUpdate {
+                        status: status.to_string(),
+                        message: Some(msg),
+                        code: Some(code.to_string()),
+                        ..Default::default()
+                    });
+                }
+            };
+
+            let emit_phase = {
+                let send_update = send_update_clone.clone();
+                move |status: &str, msg: &str, url: Option<String>, phase: TunnelPhase| {
+                    send_update(CloudflareStatusUpdate {
+                        status: status.to_string(),
+                        message: Some(msg.to_string()),
+                        url,
+                        phase: Some(phase),
+                        ..Default::default()
+                    });
+                }
+            };
+
+            emit_phase("connecting", "Starting tunnel...", None, TunnelPhase::LocatingBinary);
+
+            // Lookup and origin checks can stall (slow PATH search, a hung network mount),
+            // so a stop during startup cancels them instead of waiting them out
+            let startup = async {
+                // Find cloudflared binary - check common installation paths
+                // GUI apps on macOS don't inherit terminal PATH, so we need to check manually.
+                // This runs once per start; retries below reuse the resolved path.
+                let cloudflared_path = match cloudflared_bin {
+                    Some(bin) => Some(bin),
+                    None => tauri::async_runtime::spawn_blocking(find_cloudflared_path).await.ok().flatten(),
+                };
+                let Some(cloudflared_bin) = cloudflared_path else {
+                    emit_coded("error", CODE_BINARY_NOT_FOUND, "cloudflared not found. Please install it first.".into());
+                    return None;
+                };
+
+                // An unknown flag makes cloudflared exit straight away, so catch the known
+                // case before spawning. Unparseable versions (dev builds) are let through.
+                if config.post_quantum {
+                    let version = cloudflared_version(&cloudflared_bin).await;
+                    if let Some(version) = version.as_deref().and_then(parse_cloudflared_version) {
+                        if version < MIN_POST_QUANTUM_VERSION {
+                            emit_coded("error", CODE_UNSUPPORTED_FLAG, unsupported_flag_message("post-quantum"));
+                            return None;
+                        }
+                    }
+                }
+
+                if let Err(e) = prepare_generated_config(&config).await {
+                    emit_coded("error", CODE_INVALID_CONFIG, e);
+                    return None;
+                }
+
+                // Direct probes say nothing about a path through a proxy, so skip them then.
+                // The system proxy is only looked up per spawn, so the toggle counts.
+                let has_proxy = config.egress_proxy.as_deref().is_some_and(|p| !p.trim().is_empty())
+                    || config.use_system_proxy;
+                if config.edge_preflight && !has_proxy {
+                    task_sink.emit_preflight(edge_preflight(&config.id).await);
+                }
+
+                if config.metrics_exposed() {
+                    emit_coded("warning", CODE_METRICS_EXPOSED, format!(
+                        "Metrics and diagnostics for this tunnel are served on {}, which other machines can reach - make sure a firewall limits who can",
+                        config.metrics_addr.as_deref().unwrap_or("").trim()
+                    ));
+                }
+
+                if config.no_tls_verify {
+                    emit_coded("warning", CODE_TLS_VERIFY_DISABLED,
+                        "Origin certificates aren't verified for this tunnel, so anyone on the path to the origin can impersonate it".to_string());
+                }
+
+                if let Err(e) = check_working_dir(&config).await {
+                    emit_coded("error", CODE_INVALID_CONFIG, e);
+                    return None;
+                }
 
-        let emit_status_clone = emit_status.clone();
+                if let Some(path) = external_config_file(&config) {
+                    if modified_time(&path).await.is_none() {
+                        emit_coded("error", CODE_INVALID_CONFIG, format!("Config file {} doesn't exist", path.display()));
+                        return None;
+                    }
+                }
 
-        let handle = tauri::async_runtime::spawn(async move {
-            emit_status_clone("connecting", Some("Starting tunnel...".into()), None);
-            
-            // Find cloudflared binary - check common installation paths
-            // GUI apps on macOS don't inherit terminal PATH, so we need to check manually
-            let cloudflared_path = find_cloudflared_path();
-            
-            if cloudflared_path.is_none() {
-                emit_status_clone("error", Some("cloudflared not found. Please install it first.".into()), None);
-                return;
-            }
-            let cloudflared_bin = cloudflared_path.unwrap();
+                // cloudflared only notices a missing socket once a request comes in, so check
+                // it up front rather than publishing a tunnel that can't serve anything
+                if config.origin_protocol == TunnelProtocol::Unix {
+                    let socket = config.unix_socket.clone().unwrap_or_default();
+                    let socket_path = std::path::PathBuf::from(socket.trim());
+                    let exists = tauri::async_runtime::spawn_blocking(move || socket_path.exists())
+                        .await
+                        .unwrap_or(false);
+                    if !exists {
+                        emit_coded("error", CODE_SOCKET_NOT_FOUND, format!("No socket at {} - start your local service first, or check the path", socket.trim()));
+                        return None;
+                    }
+                }
+
+                // Warn early if the origin isn't up yet - the tunnel still starts since
+                // the local server may come up later
+                let uses_port = config.origin_protocol != TunnelProtocol::Unix;
+                let port_listening = uses_port && is_port_listening(&config.origin_host(), config.local_port).await;
+                if config.check_local_port && uses_port && !port_listening {
+                    emit_coded("warning", CODE_PORT_REFUSED, format!("Nothing is listening on port {}", config.local_port));
+                }
+
+                // Quick tunnels publish whatever holds the port, so tell the user who that is
+                if config.check_port_owner && config.tunnel_token.is_empty() && port_listening {
+                    let owner = find_port_owner(config.local_port).await
+                        .unwrap_or_else(|| "another process".to_string());
+                    emit_coded("warning", CODE_PORT_IN_USE, format!("Port {} is already in use by {} - make sure this is the service you want to expose", config.local_port, owner));
+                }
+
+                Some(cloudflared_bin)
+            };
+            let cloudflared_bin = tokio::select! {
+                bin = startup => match bin {
+                    Some(bin) => bin,
+                    None => return,
+                },
+                _ = notify_clone.notified() => {
+                    stop_requested.store(true, Ordering::SeqCst);
+                    emit_stopped();
+                    return;
+                }
+            };
             
+            let log_file = if config.log_to_file {
+                spawn_log_writer(&config.id)
+            } else {
+                None
+            };
+
+            // Always running so stream_logs can be switched on while the tunnel is up
+            let log_stream = spawn_log_streamer(task_sink.clone(), config.id.clone());
+
             let mut retry_count = 0;
+            let mut retry_message;
             const MAX_RETRIES: u32 = 3;
+            let mut last_version: Option<String> = None;
+            // The config the last spawn used; starts as the one this task was given
+            let mut current = config.clone();
             
             loop {
-                // For named tunnels with tokens from Cloudflare Dashboard:
-                // The ingress rules (including URL routing) are configured in the dashboard
-                // So we only need: cloudflared tunnel run --token <token>
-                // 
-                // For quick tunnels (no token, just expose a port):
-                // cloudflared tunnel --url http://localhost:<port>
-                let mut cmd = Command::new(&cloudflared_bin);
-                
-                if config.tunnel_token.is_empty() {
-                    // Quick tunnel mode - expose local port directly
-                    cmd.arg("tunnel");
-                    cmd.arg("--url");
-                    cmd.arg(format!("http://localhost:{}", config.local_port));
-                } else {
-                    // Named tunnel mode - use token from dashboard
-                    // Ingress rules are configured in Cloudflare Zero Trust dashboard
-                    cmd.arg("tunnel");
-                    cmd.arg("run");
-                    cmd.arg("--token");
-                    cmd.arg(&config.tunnel_token);
+                // Spawn with the stored config, which update_config may have replaced
+                // while this loop was backing off
+                let latest = lock_recover(&stored_tunnels)
+                    .get(&current.id)
+                    .filter(|t| t.generation == generation && t.config != current)
+                    .map(|t| t.config.clone());
+                if let Some(latest) = latest {
+                    if let Err(e) = prepare_generated_config(&latest).await {
+                        emit_coded("error", CODE_INVALID_CONFIG, e);
+                        break;
+                    }
+                    current = latest;
                 }
+                let mut config = current.clone();
+                apply_system_proxy(&mut config).await;
 
-                emit_status_clone("connecting", Some(format!("Connecting to port {}...", config.local_port)), None);
-
-                cmd.stdout(std::process::Stdio::piped())
-                   .stderr(std::process::Stdio::piped())
-                   .stdin(std::process::Stdio::null());
-                   
-                #[cfg(windows)]
+                // A fresh process gets a fresh quick-tunnel URL, so drop the stale one
                 {
-                    const CREATE_NO_WINDOW: u32 = 0x08000000;
-                    cmd.creation_flags(CREATE_NO_WINDOW);
+                    let mut runtime = lock_recover(&runtime_clone);
+                    runtime.url = None;
+                    runtime.connector_id = None;
+                    if runtime.spawn_attempts > 0 {
+                        runtime.record_disruption();
+                    }
+                    runtime.connect_ms = None;
+                    runtime.edge_connections = 0;
+                    runtime.expected_connections = expected_connections(&config);
+                    runtime.spawn_attempts += 1;
+                    runtime.spawned_at = Some(Instant::now());
+                }
+
+                // Without --no-autoupdate cloudflared can replace its own binary, and the
+                // next process then behaves like a different release
+                if let Some(version) = spawn_version(&cloudflared_bin).await {
+                    if let Some(previous) = last_version.as_deref().filter(|v| *v != version) {
+                        let hint = if config.no_autoupdate { "" } else { " - turn on no-autoupdate to keep the version fixed" };
+                        emit_coded("warning", CODE_VERSION_CHANGED, format!(
+                            "cloudflared was updated from {} to {} since the last start{}",
+                            previous, version, hint
+                        ));
+                    }
+                    last_version = Some(version);
                 }
 
-                cmd.kill_on_drop(true);
+                let mut cmd = build_command(&cloudflared_bin, &config);
+                // Taken before the spawn so an edit made while cloudflared starts isn't missed
+                let watched_file = external_config_file(&config).filter(|_| config.watch_config_file);
+                let watched_since = match &watched_file {
+                    Some(path) => modified_time(path).await,
+                    None => None,
+                };
+
+                emit_phase("connecting", &format!("Connecting to port {}...", config.local_port), None, TunnelPhase::Spawning);
+
+                configure_process(&mut cmd);
+
+                // Spawning blocks while the OS loads the binary; keep Stop responsive. A
+                // child that appears after we've given up is dropped, and kill_on_drop reaps it.
+                let spawning = tauri::async_runtime::spawn_blocking(move || cmd.spawn());
+                let spawn_result = tokio::select! {
+                    result = spawning => result.unwrap_or_else(|e| Err(std::io::Error::other(e.to_string()))),
+                    _ = notify_clone.notified() => {
+                        stop_requested.store(true, Ordering::SeqCst);
+                        emit_stopped();
+                        break;
+                    }
+                };
 
-                match cmd.spawn() {
+                match spawn_result {
                     Ok(mut child) => {
-                        emit_status_clone("connecting", Some("Authenticating...".into()), None);
+                        emit_phase("connecting", "Authenticating...", None, TunnelPhase::Authenticating);
                         
-                        let stderr = child.stderr.take();
-                        let emit_output = emit_status_clone.clone();
+                        // cloudflared writes to both streams depending on version/subcommand
+                        // (the quick-tunnel banner sometimes lands on stdout), so merge them
+                        let (line_tx, mut line_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+                        if let Some(stderr) = child.stderr.take() {
+                            spawn_line_pump(stderr, line_tx.clone());
+                        }
+                        if let Some(stdout) = child.stdout.take() {
+                            spawn_line_pump(stdout, line_tx.clone());
+                        }
+                        drop(line_tx);
+
+                        let emit_output = emit_phase.clone();
+                        let total_connections = expected_connections(&config);
+                        let emit_coded_output = emit_coded.clone();
+                        let reader_send_update = send_update_clone.clone();
                         let is_connected = Arc::new(AtomicBool::new(false));
                         let is_connected_clone = is_connected.clone();
+                        let auth_failure: Arc<Mutex<Option<&'static str>>> = Arc::new(Mutex::new(None));
+                        let reader_auth_failure = auth_failure.clone();
+                        let tunnel_in_use = Arc::new(AtomicBool::new(false));
+                        let reader_tunnel_in_use = tunnel_in_use.clone();
+                        let unsupported_flag: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
+                        let reader_unsupported_flag = unsupported_flag.clone();
+                        // Set when we start taking this process down (stop, forced reconnect,
+                        // reload), so its shutdown chatter isn't reported as errors
+                        let terminating = Arc::new(AtomicBool::new(false));
+                        let reader_terminating = terminating.clone();
+                        let reader_runtime = runtime_clone.clone();
+                        let reader_id = config.id.clone();
+                        let reader_log_file = log_file.clone();
+                        let reader_log_stream = log_stream.clone();
+                        let reader_token = config.tunnel_token.clone();
+                        let reader_has_proxy = config.egress_proxy.as_deref().is_some_and(|p| !p.trim().is_empty())
+                            || config.env.keys().any(|k| k.eq_ignore_ascii_case("https_proxy") || k.eq_ignore_ascii_case("all_proxy"));
                         
-                        // Spawn a task to read stderr and detect connection status
-                        let stderr_reader = tauri::async_runtime::spawn(async move {
-                            use tokio::io::{AsyncBufReadExt, BufReader};
-                            
+                        // Spawn a task to read cloudflared output and detect connection status
+                        let output_reader = tauri::async_runtime::spawn(async move {
                             let mut detected_url: Option<String> = None;
+                            let mut registered_conns: Vec<u32> = Vec::new();
+                            let registration_phase = |registered: u32| {
+                                if registered >= total_connections {
+                                    TunnelPhase::Ready
+                                } else {
+                                    TunnelPhase::RegisteringConnections { registered, total: total_connections }
+                                }
+                            };
+                            let mut origin_warned = false;
+                            let mut edge_warned = false;
+                            let mut deprecations: Vec<String> = Vec::new();
                             
-                            if let Some(stderr) = stderr {
-                                let reader = BufReader::new(stderr);
-                                let mut lines = reader.lines();
+                            while let Some(raw_line) = line_rx.recv().await {
+                                // Some Windows builds emit CRLF; strip the trailing \r so URL
+                                // extraction and the contains() checks see clean text
+                                let line = redact_secrets(raw_line.trim_end(), &reader_token);
+                                let line = line.as_str();
                                 
-                                while let Ok(Some(line)) = lines.next_line().await {
-                                    let line_lower = line.to_lowercase();
-                                    
-                                    // Debug: log all lines for troubleshooting
-                                    #[cfg(debug_assertions)]
-                                    println!("[cloudflared] {}", line);
-                                    
-                                    // Detect successful connection - cloudflared logs these on success:
-                                    // "INF Connection ... registered connIndex=..."
-                                    // "INF Registered tunnel connection connIndex=..."
-                                    if line_lower.contains("registered") && 
-                                       (line_lower.contains("connection") || line_lower.contains("connindex")) {
+                                // Debug: log all lines for troubleshooting
+                                #[cfg(debug_assertions)]
+                                println!("[cloudflared] {}", line);
+
+                                let stream_logs = {
+                                    let mut runtime = lock_recover(&reader_runtime);
+                                    runtime.push_log(line);
+                                    runtime.stream_logs
+                                };
+                                if let Some(log_file) = &reader_log_file {
+                                    let _ = log_file.send(line.to_string());
+                                }
+                                if stream_logs {
+                                    let _ = reader_log_stream.send(line.to_string());
+                                }
+                                
+                                let event = match classify_line(line) {
+                                    // With a proxy configured, an unreachable edge is most likely the proxy
+                                    Some(StatusEvent::Error { code: CODE_EDGE_UNREACHABLE, .. }) if reader_has_proxy => {
+                                        Some(StatusEvent::ProxyFailure)
+                                    }
+                                    Some(StatusEvent::Canceled { .. }) if reader_terminating.load(Ordering::SeqCst) => None,
+                                    Some(StatusEvent::Canceled { fatal }) => {
+                                        Some(StatusEvent::Error { fatal, code: CODE_CLOUDFLARED_ERROR })
+                                    }
+                                    other => other,
+                                };
+                                match event {
+                                    Some(StatusEvent::Registered) => {
+                                        is_connected_clone.store(true, Ordering::SeqCst);
+                                        lock_recover(&reader_runtime).mark_connected();
+                                        let conn = parse_conn_index(line).unwrap_or(registered_conns.len() as u32);
+                                        if !registered_conns.contains(&conn) {
+                                            registered_conns.push(conn);
+                                        }
+                                        lock_recover(&reader_runtime).edge_connections = registered_conns.len() as u32;
+                                        let phase = registration_phase(registered_conns.len() as u32);
+                                        emit_output("connected", "Tunnel established", detected_url.clone(), phase);
+                                    }
+                                    // cloudflared re-dials dropped edge connections itself, so these are
+                                    // only reported; respawning is left to the process-exit path
+                                    Some(StatusEvent::EdgeDisconnected) => {
+                                        match parse_conn_index(line) {
+                                            Some(conn) => registered_conns.retain(|c| *c != conn),
+                                            None => {
+                                                registered_conns.pop();
+                                            }
+                                        }
+                                        {
+                                            let mut runtime = lock_recover(&reader_runtime);
+                                            runtime.edge_connections = registered_conns.len() as u32;
+                                            runtime.record_disruption();
+                                        }
+                                        if registered_conns.is_empty() {
+                                            lock_recover(&reader_runtime).connected_since = None;
+                                            emit_output("reconnecting", "Lost every edge connection, cloudflared is reconnecting...", detected_url.clone(), registration_phase(0));
+                                        } else {
+                                            emit_coded_output("warning", CODE_EDGE_RECONNECTING, format!("An edge connection dropped, cloudflared is reconnecting it ({}/{} still up)", registered_conns.len(), total_connections));
+                                        }
+                                    }
+                                    Some(StatusEvent::EdgeRetry(retry_in_secs)) => {
+                                        // With other connections still serving, the warning above said enough
+                                        if registered_conns.is_empty() {
+                                            let was_connected = is_connected_clone.load(Ordering::SeqCst);
+                                            reader_send_update(CloudflareStatusUpdate {
+                                                status: if was_connected { "reconnecting" } else { "connecting" }.to_string(),
+                                                message: Some("cloudflared is retrying the edge connection...".to_string()),
+                                                retry_in_secs,
+                                                code: Some(CODE_EDGE_RECONNECTING.to_string()),
+                                                ..Default::default()
+                                            });
+                                        }
+                                    }
+                                    // Carried on every later update rather than sent on their own
+                                    Some(StatusEvent::TunnelId(id)) => {
+                                        lock_recover(&reader_runtime).tunnel_id = Some(id);
+                                    }
+                                    Some(StatusEvent::ConnectorId(id)) => {
+                                        lock_recover(&reader_runtime).connector_id = Some(id);
+                                    }
+                                    Some(StatusEvent::TunnelUrl(url)) => {
+                                        detected_url = Some(url.clone());
+                                        lock_recover(&reader_runtime).url = detected_url.clone();
+                                        let id = reader_id.clone();
+                                        tauri::async_runtime::spawn_blocking(move || persist_tunnel_url(&id, Some(&url)));
                                         is_connected_clone.store(true, Ordering::SeqCst);
-                                        emit_output("connected", Some("Tunnel established".into()), detected_url.clone());
-                                    } 
-                                    // Quick tunnel URL detection
-                                    else if line.contains(".trycloudflare.com") || line.contains(".cfargotunnel.com") {
-                                        if let Some(url_start) = line.find("https://") {
-                                            let url = line[url_start..].split_whitespace().next().unwrap_or("");
-                                            detected_url = Some(url.to_string());
-                                            is_connected_clone.store(true, Ordering::SeqCst);
-                                            emit_output("connected", Some("Tunnel ready".into()), detected_url.clone());
+                                        lock_recover(&reader_runtime).mark_connected();
+                                        let phase = registration_phase(registered_conns.len() as u32);
+                                        emit_output("connected", "Tunnel ready", detected_url.clone(), phase);
+                                    }
+                                    // Tunnel is up but the local app isn't - report once per process so
+                                    // every failed request doesn't re-emit
+                                    Some(StatusEvent::OriginRefused(port)) => {
+                                        if !origin_warned {
+                                            origin_warned = true;
+                                            emit_coded_output("origin_unreachable", CODE_PORT_REFUSED, format!("Nothing is listening on port {} - is your local app running?", port));
+                                        }
+                                    }
+                                    // Edge unreachable through the proxy - say so once instead of
+                                    // letting it read as generic reconnect churn
+                                    Some(StatusEvent::ProxyFailure) => {
+                                        if !edge_warned {
+                                            edge_warned = true;
+                                            emit_coded_output("error", CODE_EDGE_UNREACHABLE, "Can't reach the Cloudflare edge through the egress proxy - check the proxy address and that it allows CONNECT".to_string());
+                                        }
+                                    }
+                                    // Remember it for the exit handler, which stops retrying
+                                    Some(StatusEvent::AuthFailed { code }) => {
+                                        let first = lock_recover(&reader_auth_failure).replace(code).is_none();
+                                        if first {
+                                            emit_coded_output("error", code, auth_failure_message(code).to_string());
+                                        }
+                                    }
+                                    // Reported by the exit handler, which stops retrying
+                                    Some(StatusEvent::UnsupportedFlag(flag)) => {
+                                        lock_recover(&reader_unsupported_flag).get_or_insert(flag);
+                                    }
+                                    // Worth fixing in the config, so each notice is passed on once
+                                    Some(StatusEvent::Deprecated(message)) => {
+                                        if !deprecations.contains(&message) {
+                                            emit_coded_output("warning", CODE_DEPRECATED, format!("cloudflared: {}", message));
+                                            deprecations.push(message);
+                                        }
+                                    }
+                                    Some(StatusEvent::TunnelInUse) => {
+                                        if !reader_tunnel_in_use.swap(true, Ordering::SeqCst) {
+                                            emit_coded_output("warning", CODE_TUNNEL_IN_USE, TUNNEL_IN_USE_MESSAGE.to_string());
                                         }
                                     }
-                                    // Detect errors (but ignore config info containing "error" word)
-                                    else if line_lower.contains("err ") || 
-                                            (line_lower.contains("failed") && !line_lower.contains("failed to parse")) ||
-                                            line_lower.contains("unable to") {
-                                        emit_output("error", Some(line.clone()), None);
+                                    // ERR lines (a dropped connIndex, a failed request) don't stop the
+                                    // process, so they're warnings; only FTL means cloudflared is going down
+                                    Some(StatusEvent::Error { fatal, code }) => {
+                                        let status = if fatal { "error" } else { "warning" };
+                                        emit_coded_output(status, code, line.to_string());
                                     }
-                                    // Connector established
-                                    else if line_lower.contains("initial protocol") || 
-                                            line_lower.contains("connection established") {
+                                    Some(StatusEvent::Connected) => {
                                         is_connected_clone.store(true, Ordering::SeqCst);
-                                        emit_output("connected", Some("Tunnel connected".into()), detected_url.clone());
+                                        lock_recover(&reader_runtime).mark_connected();
+                                        emit_output("connected", "Tunnel connected", detected_url.clone(), TunnelPhase::Ready);
                                     }
+                                    None => {}
                                 }
                             }
                         });
 
-                        // Wait for either: process exit, stop signal
+                        // The shared monitor only scrapes tunnels whose process is up
+                        {
+                            let mut runtime = lock_recover(&runtime_clone);
+                            runtime.process_running = true;
+                            runtime.pid = child.id();
+                        }
+
+                        let config_changed = async {
+                            match &watched_file {
+                                Some(path) => config_file_changed(path, watched_since).await,
+                                None => std::future::pending().await,
+                            }
+                        };
+
+                        // Warns once if the process is still not connected by the deadline, then
+                        // either keeps waiting or resolves so the process gets replaced
+                        let connect_timeout = async {
+                            if config.connect_timeout_secs == 0 {
+                                return std::future::pending().await;
+                            }
+                            tokio::time::sleep(Duration::from_secs(config.connect_timeout_secs)).await;
+                            if is_connected.load(Ordering::SeqCst) {
+                                return std::future::pending().await;
+                            }
+                            emit_coded("warning", CODE_CONNECT_TIMEOUT, format!(
+                                "Still not connected after {}s - check the tunnel token and that this network allows outbound connections to Cloudflare on port 7844",
+                                config.connect_timeout_secs
+                            ));
+                            if !config.restart_on_connect_timeout {
+                                std::future::pending::<()>().await;
+                            }
+                        };
+
+                        // Wait for either: process exit, stop signal, reconnect, config change
+                        // or a stalled connect
                         tokio::select! {
                             exit_status = child.wait() => {
-                                stderr_reader.abort();
+                                output_reader.abort();
+                                {
+                                    let mut runtime = lock_recover(&runtime_clone);
+                                    runtime.process_running = false;
+                                    runtime.pid = None;
+                                    runtime.connected_since = None;
+                                }
+                                // A rejected token won't start working on retry - stop here so the
+                                // user can refresh it instead of watching reconnects spin
+                                let auth_failure = *lock_recover(&auth_failure);
+                                if let Some(code) = auth_failure {
+                                    emit_coded("error", code, auth_failure_message(code).to_string());
+                                    break;
+                                }
+                                // Same binary, same flags: respawning would fail the same way
+                                let unsupported_flag = lock_recover(&unsupported_flag).take();
+                                if let Some(flag) = unsupported_flag {
+                                    emit_coded("error", CODE_UNSUPPORTED_FLAG, unsupported_flag_message(&flag));
+                                    break;
+                                }
+                                // Never got a connection because another connector holds the tunnel;
+                                // reconnecting just races it again
+                                if tunnel_in_use.load(Ordering::SeqCst) && !is_connected.load(Ordering::SeqCst) {
+                                    emit_coded("error", CODE_TUNNEL_IN_USE, TUNNEL_IN_USE_MESSAGE.to_string());
+                                    break;
+                                }
+
+                                // A failed exit we're about to retry is only a warning; "error"
+                                // means the tunnel is staying down
+                                let will_retry = config.auto_reconnect
+                                    && (is_connected.load(Ordering::SeqCst) || retry_count < MAX_RETRIES);
+                                let failure_status = if will_retry { "warning" } else { "error" };
                                 match exit_status {
                                     Ok(status) => {
                                         if status.success() {
                                             emit_status_clone("disconnected", Some("Tunnel closed".into()), None);
                                         } else {
-                                            let code = status.code().unwrap_or(-1);
-                                            emit_status_clone("error", Some(format!("Exit code: {}", code)), None);
+                                            let (code, message) = exit_failure(status);
+                                            emit_coded(failure_status, &code, message);
                                         }
                                     }
                                     Err(e) => {
-                                        emit_status_clone("error", Some(format!("Process error: {}", e)), None);
+                                        emit_coded(failure_status, CODE_SPAWN_FAILED, format!("Process error: {}", e));
                                     }
                                 }
                                 
+                                if !config.auto_reconnect {
+                                    break;
+                                }
+
                                 // Only retry if we were connected (unexpected disconnect)
                                 // or if we haven't exceeded retry count
                                 if is_connected.load(Ordering::SeqCst) {
                                     // Was connected, retry to reconnect
                                     retry_count = 0;
-                                    emit_status_clone("reconnecting", Some("Connection lost, reconnecting...".into()), None);
+                                    retry_message = "Connection lost, reconnecting...".to_string();
                                 } else if retry_count < MAX_RETRIES {
                                     retry_count += 1;
-                                    emit_status_clone("reconnecting", Some(format!("Retrying ({}/{})...", retry_count, MAX_RETRIES)), None);
+                                    retry_message = format!("Retrying ({}/{})...", retry_count, MAX_RETRIES);
                                 } else {
-                                    emit_status_clone("error", Some("Failed to connect after multiple attempts".into()), None);
+                                    emit_coded("error", CODE_RETRIES_EXHAUSTED, "Failed to connect after multiple attempts".into());
                                     break;
                                 }
                             }
                             _ = notify_clone.notified() => {
-                                let _ = child.kill().await;
-                                stderr_reader.abort();
-                                emit_status_clone("disconnected", Some("Tunnel stopped".into()), None);
+                                stop_requested.store(true, Ordering::SeqCst);
+                                terminating.store(true, Ordering::SeqCst);
+                                emit_status_clone("disconnecting", Some("Stopping tunnel...".into()), None);
+                                // The reader keeps draining the pipes while cloudflared shuts down
+                                // so it never blocks on a full pipe; its events are dropped by now
+                                terminate_gracefully(&mut child, Duration::from_secs(config.shutdown_grace_period_secs)).await;
+                                output_reader.abort();
+                                let _ = output_reader.await;
+                                {
+                                    let mut runtime = lock_recover(&runtime_clone);
+                                    runtime.process_running = false;
+                                    runtime.pid = None;
+                                    runtime.connected_since = None;
+                                }
+                                emit_stopped();
                                 break;
                             }
+                            _ = reconnect_clone.notified() => {
+                                // Forced reconnect: the process may be wedged, so don't wait on a drain
+                                terminating.store(true, Ordering::SeqCst);
+                                emit_status_clone("reconnecting", Some("Reconnecting...".into()), None);
+                                terminate_gracefully(&mut child, Duration::ZERO).await;
+                                output_reader.abort();
+                                {
+                                    let mut runtime = lock_recover(&runtime_clone);
+                                    runtime.process_running = false;
+                                    runtime.pid = None;
+                                    runtime.connected_since = None;
+                                }
+                                retry_count = 0;
+                                continue;
+                            }
+                            _ = connect_timeout => {
+                                terminating.store(true, Ordering::SeqCst);
+                                terminate_gracefully(&mut child, Duration::ZERO).await;
+                                output_reader.abort();
+                                {
+                                    let mut runtime = lock_recover(&runtime_clone);
+                                    runtime.process_running = false;
+                                    runtime.pid = None;
+                                    runtime.connected_since = None;
+                                }
+                                if !config.auto_reconnect {
+                                    emit_coded("error", CODE_CONNECT_TIMEOUT, format!("Gave up after {}s without a connection", config.connect_timeout_secs));
+                                    break;
+                                }
+                                if retry_count >= MAX_RETRIES {
+                                    emit_coded("error", CODE_RETRIES_EXHAUSTED, "Failed to connect after multiple attempts".into());
+                                    break;
+                                }
+                                retry_count += 1;
+                                retry_message = format!("Timed out connecting, retrying ({}/{})...", retry_count, MAX_RETRIES);
+                            }
+                            _ = config_changed => {
+                                // cloudflared only reads its config at startup, so swap in a new process
+                                terminating.store(true, Ordering::SeqCst);
+                                emit_status_clone("reloading", Some("Config file changed, restarting...".into()), None);
+                                terminate_gracefully(&mut child, Duration::from_secs(config.shutdown_grace_period_secs)).await;
+                                output_reader.abort();
+                                let _ = output_reader.await;
+                                {
+                                    let mut runtime = lock_recover(&runtime_clone);
+                                    runtime.process_running = false;
+                                    runtime.pid = None;
+                                    runtime.connected_since = None;
+                                }
+                                retry_count = 0;
+                                continue;
+                            }
                         }
                     },
                     Err(e) => {
+                        // The path was found before the loop, so NotFound here means the
+                        // binary went away mid-session; say which file instead of re-searching
+                        let error_code = CODE_SPAWN_FAILED;
                         let error_msg = if e.kind() == std::io::ErrorKind::NotFound {
-                            "cloudflared not found. Please install it first.".to_string()
+                            format!("cloudflared is no longer at {} - reinstall it or restart the tunnel", cloudflared_bin)
                         } else {
                             format!("Failed to start: {}", e)
                         };
-                        emit_status_clone("error", Some(error_msg), None);
-                        
-                        // Don't retry if cloudflared is not found
-                        if e.kind() == std::io::ErrorKind::NotFound {
+                        // Retrying a missing binary can't succeed
+                        if e.kind() == std::io::ErrorKind::NotFound || !config.auto_reconnect {
+                            emit_coded("error", error_code, error_msg);
                             break;
                         }
                         
                         retry_count += 1;
                         if retry_count >= MAX_RETRIES {
-                            emit_status_clone("error", Some("Failed to start after multiple attempts".into()), None);
+                            emit_coded("error", error_code, error_msg);
+                            emit_coded("error", CODE_RETRIES_EXHAUSTED, "Failed to start after multiple attempts".into());
                             break;
                         }
+                        emit_coded("warning", error_code, error_msg);
+                        retry_message = format!("Retrying start ({}/{})...", retry_count, MAX_RETRIES);
                     }
                 }
                 
-                // Wait before retry
-                tokio::select! {
-                    _ = tokio::time::sleep(Duration::from_secs(5)) => {}
-                    _ = notify_clone.notified() => {
-                        emit_status_clone("disconnected", Some("Tunnel stopped".into()), None);
-                        break;
+                // Wait before retry, counting down so the UI can show when the next attempt happens
+                lock_recover(&runtime_clone).backing_off = true;
+                let mut stopped = false;
+                for remaining in (1..=RETRY_DELAY_SECS).rev() {
+                    send_update_clone(CloudflareStatusUpdate {
+                        status: "reconnecting".to_string(),
+                        message: Some(retry_message.clone()),
+                        retry_in_secs: Some(remaining),
+                        ..Default::default()
+                    });
+                    tokio::select! {
+                        _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+                        _ = reconnect_clone.notified() => {
+                            retry_count = 0;
+                            break;
+                        }
+                        _ = config_updated_clone.notified() => {
+                            // An edited config gets a fresh set of attempts
+                            retry_count = 0;
+                            break;
+                        }
+                        _ = notify_clone.notified() => {
+                            stop_requested.store(true, Ordering::SeqCst);
+                            stopped = true;
+                            break;
+                        }
                     }
                 }
+                lock_recover(&runtime_clone).backing_off = false;
+                if stopped {
+                    emit_stopped();
+                    break;
+                }
             }
         });
 
-        tunnels.lock().unwrap().insert(config_id, RunningTunnel {
+        let previous = tunnels.insert(config_id, RunningTunnel {
+            config: stored_config,
+            runtime,
             notify_stop,
+            notify_reconnect,
+            notify_config,
             handle,
+            generation,
         });
+        if let Some(previous) = previous {
+            previous.notify_stop.notify_one();
+        }
+        Ok(())
+    }
+
+    /// Stop a tunnel and forget it. Returns false if the manager had no tunnel with `id`,
+    /// so there was nothing to stop.
+    pub fn disconnect(&self, id: &str) -> bool {
+        let mut tunnels = lock_recover(&self.tunnels);
+        match tunnels.remove(id) {
+            Some(tunnel) => {
+                tunnel.notify_stop.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// Apply an edited config to a running tunnel, respawning cloudflared only when a
+    /// field that affects the process changed. A paused tunnel only has its config
+    /// replaced, for `resume` to use. Returns true if the tunnel was restarted.
+    pub fn update_config(&self, sink: Arc<dyn StatusSink>, new_config: CloudflareConfig) -> Result<bool, String> {
+        // Compared against a running config that already has the defaults merged in
+        let mut new_config = self.with_defaults(new_config);
+        new_config.normalize();
+        new_config.validate()?;
+
+        {
+            let mut tunnels = lock_recover(&self.tunnels);
+            let Some(tunnel) = tunnels.get_mut(&new_config.id) else {
+                return Ok(false);
+            };
+            {
+                let mut runtime = lock_recover(&tunnel.runtime);
+                runtime.stream_logs = new_config.stream_logs;
+                runtime.status_webhook = new_config.status_webhook.clone();
+            }
+            if !tunnel.config.requires_restart(&new_config) {
+                tunnel.config = new_config;
+                return Ok(false);
+            }
+            // No process to replace while waiting to retry; the loop respawns with the
+            // stored config as soon as it's woken
+            if lock_recover(&tunnel.runtime).backing_off {
+                tunnel.config = new_config;
+                tunnel.notify_config.notify_one();
+                return Ok(true);
+            }
+            // Stays paused; `resume` spawns from the stored config
+            if lock_recover(&tunnel.runtime).paused {
+                tunnel.config = new_config;
+                return Ok(false);
+            }
+        }
+
+        self.connect(sink, new_config)?;
+        Ok(true)
     }
 
-    pub fn disconnect(&self, id: &str) {
-        let mut tunnels = self.tunnels.lock().unwrap();
-        if let Some(tunnel) = tunnels.remove(id) {
+    /// Stop a tunnel's cloudflared process but keep its entry and config so it can be
+    /// resumed later. Returns false if the tunnel isn't known to the manager.
+    pub fn pause(&self, id: &str) -> bool {
+        let tunnels = lock_recover(&self.tunnels);
+        let Some(tunnel) = tunnels.get(id) else {
+            return false;
+        };
+        let mut runtime = lock_recover(&tunnel.runtime);
+        if !runtime.paused {
+            runtime.paused = true;
             tunnel.notify_stop.notify_one();
         }
+        true
+    }
+
+    /// Respawn a paused tunnel from its stored config
+    pub fn resume(&self, sink: Arc<dyn StatusSink>, id: &str) -> Result<(), String> {
+        let config = {
+            let tunnels = lock_recover(&self.tunnels);
+            match tunnels.get(id) {
+                Some(tunnel) if lock_recover(&tunnel.runtime).paused => tunnel.config.clone(),
+                Some(_) => return Err("Tunnel is not paused".to_string()),
+                None => return Err("Tunnel not found".to_string()),
+            }
+        };
+        self.start_tunnel(sink, config, None)
+    }
+
+    /// Force a running tunnel to drop its cloudflared process and reconnect right away
+    /// with the config it was started with. Returns false if the tunnel isn't running.
+    pub fn reconnect(&self, id: &str) -> bool {
+        let tunnels = lock_recover(&self.tunnels);
+        match tunnels.get(id) {
+            Some(tunnel) => {
+                tunnel.notify_reconnect.notify_one();
+                true
+            }
+            None => false,
+        }
     }
     
-    #[allow(dead_code)]
-    pub fn disconnect_all(&self) {
-        println!("[Cloudflare Manager] Shutting down all tunnels...");
-        let mut tunnels = self.tunnels.lock().unwrap();
-        for (id, tunnel) in tunnels.iter() {
-            println!("[Cloudflare Manager] Stopping tunnel: {}", id);
+    /// Stop every tunnel and wait for their tasks to finish, up to `timeout` in total.
+    /// Any cloudflared process still alive after that is force-killed by PID, so this
+    /// only returns once nothing is left running. Background tasks keep running: the
+    /// scheduler can start tunnels again later, and /metrics stays up while
+    /// `cloudflare_metrics_enabled` is on.
+    pub async fn stop_all(&self, timeout: Duration) {
+        let stopping: Vec<(String, RunningTunnel)> = lock_recover(&self.tunnels).drain().collect();
+        for (id, tunnel) in &stopping {
+            println!("[Cloudflare Manager] Stopping tunnel: {} ({})", tunnel.config.name, id);
             tunnel.notify_stop.notify_one();
         }
-        tunnels.clear();
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        for (id, tunnel) in stopping {
+            if tokio::time::timeout_at(deadline, tunnel.handle).await.is_err() {
+                let pid = lock_recover(&tunnel.runtime).pid;
+                if let Some(pid) = pid {
+                    println!("[Cloudflare Manager] Tunnel {} ({}) didn't stop in time, killing pid {}", tunnel.config.name, id, pid);
+                    signal_process_group(pid, true).await;
+                }
+            }
+        }
+    }
+
+    /// `stop_all`, then end the monitor, scheduler and metrics server. For app exit and
+    /// the headless runner; nothing restarts the background tasks afterwards.
+    pub async fn shutdown(&self, timeout: Duration) {
+        println!("[Cloudflare Manager] Shutting down all tunnels...");
+        if let Some(monitor) = lock_recover(&self.monitor).take() {
+            monitor.abort();
+        }
+        if let Some(scheduler) = lock_recover(&self.scheduler).take() {
+            scheduler.abort();
+        }
+        if let Some(server) = lock_recover(&self.metrics_server).take() {
+            server.abort();
+        }
+        self.stop_all(timeout).await;
+    }
+
+    /// Find cloudflared processes this manager doesn't own and, with `confirm`, stop them:
+    /// SIGTERM (or taskkill) first, then a force kill for any still up after a few
+    /// seconds. Children of this process and of another live ProxyPal (the background
+    /// runner, a second window) are left alone; a cloudflared installed as a system
+    /// service is not, which is why the sweep needs confirming.
+    pub async fn kill_orphans(&self, confirm: bool) -> OrphanSweep {
+        let owned: Vec<u32> = lock_recover(&self.tunnels)
+            .values()
+            .filter_map(|t| lock_recover(&t.runtime).pid)
+            .collect();
+        let own_pid = std::process::id();
+        let pids: Vec<u32> = list_cloudflared_processes()
+            .await
+            .into_iter()
+            .filter(|p| !owned.contains(&p.pid) && p.parent_pid != Some(own_pid))
+            .filter(|p| !p.parent_name.as_deref().is_some_and(|n| n.to_lowercase().contains("proxypal")))
+            .map(|p| p.pid)
+            .collect();
+        if !confirm || pids.is_empty() {
+            return OrphanSweep { pids, killed: 0 };
+        }
+
+        for &pid in &pids {
+            println!("[Cloudflare Manager] Stopping orphaned cloudflared (pid {})", pid);
+            signal_process(pid, false).await;
+        }
+        tokio::time::sleep(ORPHAN_STOP_GRACE).await;
+        let mut killed = 0;
+        for &pid in &pids {
+            if process_alive(pid).await {
+                signal_process(pid, true).await;
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                if process_alive(pid).await {
+                    eprintln!("[Cloudflare Manager] Could not stop cloudflared (pid {})", pid);
+                    continue;
+                }
+            }
+            killed += 1;
+        }
+        OrphanSweep { pids, killed }
     }
 
-    #[allow(dead_code)]
-    pub fn get_status(&self, id: &str) -> String {
-       let tunnels = self.tunnels.lock().unwrap();
-       if tunnels.contains_key(id) {
-           "active".to_string()
-       } else {
-           "inactive".to_string()
+    pub fn get_status(&self, id: &str) -> CloudflareTunnelStatus {
+       let last_known_url = load_tunnel_urls().remove(id);
+       let tunnels = lock_recover(&self.tunnels);
+       match tunnels.get(id) {
+           Some(tunnel) => tunnel_status(id, tunnel, last_known_url),
+           None => inactive_status(id, last_known_url),
        }
     }
+
+    /// The public URL of a running tunnel, normalized to `https://host`. Errors until
+    /// cloudflared has reported one, or if what was captured isn't a usable https URL.
+    pub fn tunnel_url(&self, id: &str) -> Result<String, String> {
+        let runtime = lock_recover(&self.tunnels)
+            .get(id)
+            .map(|t| t.runtime.clone())
+            .ok_or_else(|| "Tunnel is not running".to_string())?;
+        let url = lock_recover(&runtime).url.clone();
+        let url = url.ok_or_else(|| "No URL has been detected for this tunnel yet".to_string())?;
+        let parsed = url::Url::parse(&url).map_err(|e| format!("Detected URL '{}' is malformed: {}", url, e))?;
+        if parsed.scheme() != "https" || !parsed.host_str().is_some_and(|h| !h.is_empty()) {
+            return Err(format!("Detected URL '{}' is not an https URL", url));
+        }
+        Ok(parsed.origin().ascii_serialization())
+    }
+
+    /// How long the tunnel's recent starts took to connect, in ms, oldest first. Kept
+    /// on disk, so it covers earlier runs too.
+    pub fn connect_times(&self, id: &str) -> Vec<u64> {
+        load_connect_times().remove(id).unwrap_or_default()
+    }
+
+    /// The last `limit` cloudflared output lines for a tunnel that pass `filter`, oldest
+    /// first. The buffer lives as long as the tunnel entry, so it's gone once the tunnel
+    /// is disconnected.
+    pub fn get_logs(&self, id: &str, limit: usize, filter: LogFilter) -> Vec<String> {
+        let tunnels = lock_recover(&self.tunnels);
+        let Some(tunnel) = tunnels.get(id) else {
+            return Vec::new();
+        };
+        let runtime = lock_recover(&tunnel.runtime);
+        let mut lines: Vec<String> = runtime
+            .logs
+            .iter()
+            .rev()
+            .filter(|line| filter.matches(line))
+            .take(limit)
+            .cloned()
+            .collect();
+        lines.reverse();
+        lines
+    }
+
+    /// Collect what a maintainer needs to debug a failing tunnel: redacted config,
+    /// cloudflared location and version, platform, status and the recent log tail.
+    /// `saved_config` is used when the tunnel isn't currently running.
+    pub async fn diagnostics(&self, id: &str, saved_config: Option<CloudflareConfig>) -> serde_json::Value {
+        let running_config = lock_recover(&self.tunnels).get(id).map(|t| t.config.clone());
+        let config = running_config.or(saved_config).map(|c| c.redacted());
+
+        let cloudflared_path = tauri::async_runtime::spawn_blocking(find_cloudflared_path)
+            .await
+            .ok()
+            .flatten();
+        let cloudflared_version = match &cloudflared_path {
+            Some(bin) => cloudflared_version(bin).await,
+            None => None,
+        };
+
+        serde_json::json!({
+            "generatedAt": chrono::Utc::now().to_rfc3339(),
+            "appVersion": env!("CARGO_PKG_VERSION"),
+            "os": std::env::consts::OS,
+            "arch": std::env::consts::ARCH,
+            "cloudflaredPath": cloudflared_path,
+            "cloudflaredVersion": cloudflared_version,
+            "config": config,
+            "status": self.get_status(id),
+            "logs": self.get_logs(id, LOG_BUFFER_LINES, LogFilter::default()),
+        })
+    }
+
+    /// Number of tunnels currently managed, whatever their state
+    pub fn running_count(&self) -> usize {
+        lock_recover(&self.tunnels).len()
+    }
+
+    /// Status of every tunnel the manager is currently running
+    pub fn list_tunnels(&self) -> Vec<CloudflareTunnelStatus> {
+        let mut urls = load_tunnel_urls();
+        let tunnels = lock_recover(&self.tunnels);
+        let mut list: Vec<CloudflareTunnelStatus> = tunnels
+            .iter()
+            .map(|(id, tunnel)| tunnel_status(id, tunnel, urls.remove(id)))
+            .collect();
+        list.sort_by(|a, b| a.id.cmp(&b.id));
+        list
+    }
+
+    /// Statuses of the tunnels tagged `tag`: running ones, plus saved configs from
+    /// `saved` that aren't running, reported as inactive with their saved name and tags
+    pub fn list_tunnels_by_tag(&self, saved: &[CloudflareConfig], tag: &str) -> Vec<CloudflareTunnelStatus> {
+        let mut urls = load_tunnel_urls();
+        let tunnels = lock_recover(&self.tunnels);
+        let mut list: Vec<CloudflareTunnelStatus> = tunnels
+            .iter()
+            .filter(|(_, tunnel)| tunnel.config.has_tag(tag))
+            .map(|(id, tunnel)| tunnel_status(id, tunnel, urls.remove(id)))
+            .collect();
+        for config in saved.iter().filter(|c| c.has_tag(tag) && !tunnels.contains_key(&c.id)) {
+            list.push(CloudflareTunnelStatus {
+                name: Some(config.name.clone()),
+                tags: config.tags.clone(),
+                ..inactive_status(&config.id, urls.remove(&config.id))
+            });
+        }
+        list.sort_by(|a, b| a.id.cmp(&b.id));
+        list
+    }
+}
+
+/// The shared monitor task: resource samples and metrics scrapes for every tunnel,
+/// woken once per METRICS_SCRAPE_INTERVAL however many tunnels there are
+async fn monitor_loop(tunnels: Arc<Mutex<HashMap<String, RunningTunnel>>>, sink: Arc<dyn StatusSink>) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(3))
+        .build()
+        .unwrap_or_default();
+    let mut ticker = tokio::time::interval(METRICS_SCRAPE_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    ticker.tick().await;
+    let mut baselines: HashMap<String, ResourceBaseline> = HashMap::new();
+    loop {
+        ticker.tick().await;
+        sample_resources(&tunnels, &mut baselines, sink.as_ref()).await;
+        let targets: Vec<(String, String)> = lock_recover(&tunnels)
+            .iter()
+            .filter(|(_, t)| {
+                let runtime = lock_recover(&t.runtime);
+                runtime.process_running && !runtime.paused
+            })
+            .filter_map(|(id, t)| {
+                let addr = t.config.metrics_addr.as_deref()?.trim();
+                (!addr.is_empty()).then(|| (id.clone(), addr.to_string()))
+            })
+            .collect();
+
+        // Scrape concurrently so one slow endpoint doesn't delay the rest
+        let mut scrapes = tokio::task::JoinSet::new();
+        for (id, addr) in targets {
+            let client = client.clone();
+            scrapes.spawn(async move {
+                let result = scrape_metrics(&client, &addr).await;
+                (id, result)
+            });
+        }
+        while let Some(joined) = scrapes.join_next().await {
+            match joined {
+                Ok((id, Ok(body))) => {
+                    let metrics = parse_metrics(&id, &body);
+                    check_idle(&tunnels, &id, metrics.total_requests);
+                    sink.emit_metrics(metrics);
+                }
+                Ok((_id, Err(_e))) => {
+                    #[cfg(debug_assertions)]
+                    println!("[cloudflared] Metrics scrape failed for {}: {}", _id, _e);
+                }
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+/// Report CPU and memory for every live cloudflared process. Baselines are rebuilt
+/// from the current tunnels each time, so stopped tunnels drop out, and a PID that now
+/// belongs to a different process only starts a new baseline.
+async fn sample_resources(
+    tunnels: &Mutex<HashMap<String, RunningTunnel>>,
+    baselines: &mut HashMap<String, ResourceBaseline>,
+    sink: &dyn StatusSink,
+) {
+    let targets: Vec<(String, u32)> = lock_recover(tunnels)
+        .iter()
+        .filter_map(|(id, t)| {
+            let runtime = lock_recover(&t.runtime);
+            let pid = runtime.pid.filter(|_| runtime.process_running && !runtime.paused)?;
+            Some((id.clone(), pid))
+        })
+        .collect();
+    let pids: Vec<u32> = targets.iter().map(|(_, pid)| *pid).collect();
+    let mut samples = sample_processes(&pids).await;
+    let now = Instant::now();
+
+    let mut next = HashMap::new();
+    for (id, pid) in targets {
+        let Some(sample) = samples.remove(&pid) else {
+            continue;
+        };
+        if let Some(prev) = baselines.remove(&id).filter(|b| b.pid == pid && b.sample.started == sample.started) {
+            let wall = now.duration_since(prev.at).as_secs_f64();
+            if wall > 0.0 {
+                let cpu = (sample.cpu_secs - prev.sample.cpu_secs).max(0.0);
+                sink.emit_resource(CloudflareResourceUpdate {
+                    id: id.clone(),
+                    cpu_percent: (cpu / wall * 1000.0).round() / 10.0,
+                    rss_bytes: sample.rss_bytes,
+                });
+            }
+        }
+        next.insert(id, ResourceBaseline { pid, sample, at: now });
+    }
+    *baselines = next;
+}
+
+/// Recount tunnel states and send FleetStatus if the counts moved
+fn publish_fleet_status(
+    tunnels: &Mutex<HashMap<String, RunningTunnel>>,
+    last_fleet: &Mutex<FleetStatus>,
+    sink: &dyn StatusSink,
+) {
+    let fleet = {
+        let tunnels = lock_recover(tunnels);
+        let mut fleet = FleetStatus {
+            total: tunnels.len(),
+            ..Default::default()
+        };
+        for tunnel in tunnels.values() {
+            match lock_recover(&tunnel.runtime).last_status.as_str() {
+                "connected" => fleet.connected += 1,
+                "error" => fleet.error += 1,
+                "reconnecting" => fleet.reconnecting += 1,
+                _ => {}
+            }
+        }
+        fleet
+    };
+    let mut last = lock_recover(last_fleet);
+    if *last != fleet {
+        *last = fleet;
+        sink.emit_fleet(fleet);
+    }
+}
+
+fn inactive_status(id: &str, last_known_url: Option<String>) -> CloudflareTunnelStatus {
+    CloudflareTunnelStatus {
+        id: id.to_string(),
+        name: None,
+        status: "inactive".to_string(),
+        mode: None,
+        local_port: None,
+        url: None,
+        last_known_url,
+        tunnel_id: None,
+        connector_id: None,
+        tags: Vec::new(),
+        quality: 0,
+    }
+}
+
+fn tunnel_status(id: &str, tunnel: &RunningTunnel, last_known_url: Option<String>) -> CloudflareTunnelStatus {
+    let mode = if tunnel.config.access.is_some() {
+        "access"
+    } else if tunnel.config.dns_proxy.is_some() {
+        "dns"
+    } else if tunnel.config.tunnel_token.is_empty() {
+        "quick"
+    } else {
+        "named"
+    };
+    let runtime = lock_recover(&tunnel.runtime);
+    CloudflareTunnelStatus {
+        id: id.to_string(),
+        name: Some(tunnel.config.name.clone()),
+        // The latest emitted status (connecting, connected, reconnecting, error...), so a
+        // tunnel that's still authenticating doesn't read as up just for being in the map
+        status: if runtime.paused {
+            "paused".to_string()
+        } else if runtime.last_status.is_empty() {
+            "connecting".to_string()
+        } else {
+            runtime.last_status.clone()
+        },
+        mode: Some(mode.to_string()),
+        local_port: Some(tunnel.config.local_port),
+        url: runtime.url.clone(),
+        last_known_url,
+        tunnel_id: runtime.tunnel_id.clone(),
+        connector_id: runtime.connector_id.clone(),
+        tags: tunnel.config.tags.clone(),
+        quality: runtime.quality(),
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Prometheus text exposition of every tunnel the manager knows about
+fn render_fleet_metrics(tunnels: &HashMap<String, RunningTunnel>) -> String {
+    let mut rows: Vec<(String, bool, u64, u64)> = tunnels
+        .iter()
+        .map(|(id, tunnel)| {
+            let runtime = lock_recover(&tunnel.runtime);
+            let labels = format!("id=\"{}\",name=\"{}\"", escape_label(id), escape_label(&tunnel.config.name));
+            let uptime = runtime.connected_since.map(|t| t.elapsed().as_secs()).unwrap_or(0);
+            (labels, runtime.connected_since.is_some(), runtime.spawn_attempts.saturating_sub(1), uptime)
+        })
+        .collect();
+    rows.sort();
+
+    let mut out = String::new();
+    out.push_str("# HELP proxypal_tunnel_up Whether the tunnel has a live edge connection\n");
+    out.push_str("# TYPE proxypal_tunnel_up gauge\n");
+    for (labels, up, _, _) in &rows {
+        out.push_str(&format!("proxypal_tunnel_up{{{}}} {}\n", labels, u8::from(*up)));
+    }
+    out.push_str("# HELP proxypal_tunnel_reconnects_total cloudflared respawns since the tunnel was started\n");
+    out.push_str("# TYPE proxypal_tunnel_reconnects_total counter\n");
+    for (labels, _, reconnects, _) in &rows {
+        out.push_str(&format!("proxypal_tunnel_reconnects_total{{{}}} {}\n", labels, reconnects));
+    }
+    out.push_str("# HELP proxypal_tunnel_uptime_seconds Seconds the current connection has been up\n");
+    out.push_str("# TYPE proxypal_tunnel_uptime_seconds gauge\n");
+    for (labels, _, _, uptime) in &rows {
+        out.push_str(&format!("proxypal_tunnel_uptime_seconds{{{}}} {}\n", labels, uptime));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_line_cases() {
+        let cases: &[(&str, Option<StatusEvent>)] = &[
+            // Registration, both historical phrasings
+            (
+                "2024-05-01T10:00:00Z INF Registered tunnel connection connIndex=0 connection=4f1c2b1e event=0 ip=198.41.200.13 location=sjc07 protocol=quic",
+                Some(StatusEvent::Registered),
+            ),
+            (
+                "2023-02-10T08:12:44Z INF Connection 7e0b6a65-33ab-4f7c-9d0e-3d0c1a7c2e11 registered connIndex=1 ip=198.41.192.27 location=LAX",
+                Some(StatusEvent::Registered),
+            ),
+            // cloudflared's own edge reconnects; "Unregistered" must not read as registered
+            (
+                "2024-05-01T10:05:00Z INF Unregistered tunnel connection connIndex=2 event=0 ip=198.41.200.43",
+                Some(StatusEvent::EdgeDisconnected),
+            ),
+            (
+                "2024-05-01T10:05:00Z INF Retrying connection in up to 4s connIndex=2 event=0 ip=198.41.200.43",
+                Some(StatusEvent::EdgeRetry(Some(4))),
+            ),
+            // Quick-tunnel banner, URL boxed between pipes
+            (
+                "2024-05-01T10:00:00Z INF |  https://Random-Words-Here.trycloudflare.com                                              |",
+                Some(StatusEvent::TunnelUrl("https://random-words-here.trycloudflare.com".to_string())),
+            ),
+            (
+                "2024-05-01T10:00:00Z INF +--------------------------------------------------------------------------------------------+",
+                None,
+            ),
+            // Named tunnel: URL from the ingress the dashboard pushes, wildcards skipped
+            (
+                r#"2024-05-01T10:00:01Z INF Updated to new configuration config="{\"ingress\":[{\"hostname\":\"*.example.com\", \"service\":\"http://localhost:8080\"}, {\"hostname\":\"App.example.com\", \"originRequest\":{}, \"service\":\"http://localhost:8317\"}, {\"service\":\"http_status:404\"}], \"warp-routing\":{\"enabled\":false}}" version=3"#,
+                Some(StatusEvent::TunnelUrl("https://app.example.com".to_string())),
+            ),
+            // Origin refused, IPv4 and bracketed IPv6
+            (
+                "2024-05-01T10:00:05Z ERR  error=\"Unable to reach the origin service. The service may be down or it may not be responding to traffic from cloudflared: dial tcp 127.0.0.1:8317: connect: connection refused\" cfRay=88d5a1b2c3d4-SJC originService=http://127.0.0.1:8317",
+                Some(StatusEvent::OriginRefused(8317)),
+            ),
+            (
+                "2024-05-01T10:00:05Z ERR Request failed error=\"dial tcp [::1]:3000: connect: connection refused\" connIndex=0",
+                Some(StatusEvent::OriginRefused(3000)),
+            ),
+            // Autoupdate and version notices are informational
+            (
+                "2024-05-01T10:00:00Z INF cloudflared will not automatically update when run from the shell. To enable auto-updates, run cloudflared as a service: https://developers.cloudflare.com/cloudflare-one/connections/connect-apps/run-tunnel/as-a-service/",
+                None,
+            ),
+            (
+                "2024-05-01T10:00:00Z WRN Your version 2023.5.0 is outdated. We recommend upgrading it to 2024.4.1",
+                None,
+            ),
+            // "error"/"failed" inside hostnames or settings must not be flagged
+            (
+                "2024-05-01T10:00:00Z INF Starting tunnel tunnelID=0d6f7c3e hostname=error-pages.example.com",
+                None,
+            ),
+            (
+                "2024-05-01T10:00:00Z INF Settings: map[no-autoupdate:true url:http://failed-builds.local:8080]",
+                None,
+            ),
+            // Real errors, classified by level prefix
+            (
+                "2024-05-01T10:00:09Z ERR Failed to serve quic connection error=\"timeout: no recent network activity\" connIndex=0 event=0 ip=198.41.200.13",
+                Some(StatusEvent::Error { fatal: false, code: CODE_CLOUDFLARED_ERROR }),
+            ),
+            (
+                "2024-05-01T10:00:09Z ERR Failed to dial to edge with quic: dial udp 198.41.200.13:7844: i/o timeout",
+                Some(StatusEvent::Error { fatal: false, code: CODE_EDGE_UNREACHABLE }),
+            ),
+            (
+                "2024-05-01T10:00:00Z FTL Provided Tunnel token is not valid.",
+                Some(StatusEvent::AuthFailed { code: CODE_INVALID_TOKEN }),
+            ),
+            (
+                "2024-05-01T10:00:02Z ERR Register tunnel error from server side error=\"Unauthorized: Invalid tunnel secret\" connIndex=0",
+                Some(StatusEvent::AuthFailed { code: CODE_INVALID_TOKEN }),
+            ),
+            (
+                "2024-05-01T10:00:02Z ERR Failed to authenticate: tunnel token expired connIndex=0",
+                Some(StatusEvent::AuthFailed { code: CODE_TOKEN_EXPIRED }),
+            ),
+            (
+                "2024-05-01T10:00:09Z ERR Unable to establish connection with Cloudflare edge error=\"proxyconnect tcp: dial tcp 10.0.0.1:3128: connect: connection refused\"",
+                Some(StatusEvent::ProxyFailure),
+            ),
+            // Same token already connected from another process or machine
+            (
+                "2024-05-01T10:00:02Z ERR Register tunnel error from server side error=\"DupConnRegisterTunnelError\" connIndex=0",
+                Some(StatusEvent::TunnelInUse),
+            ),
+            (
+                "2024-05-01T10:00:02Z WRN Unable to establish connection with edge: already connected to this server, trying another address connIndex=1",
+                Some(StatusEvent::TunnelInUse),
+            ),
+            // Identity lines; the short id in the hostname case above isn't a UUID
+            (
+                "2024-05-01T10:00:00Z INF Starting tunnel tunnelID=0D6F7C3E-1B2A-4C5D-8E9F-0A1B2C3D4E5F",
+                Some(StatusEvent::TunnelId("0d6f7c3e-1b2a-4c5d-8e9f-0a1b2c3d4e5f".to_string())),
+            ),
+            (
+                "2024-05-01T10:00:00Z INF Generated Connector ID: 7f1c2b3a-4d5e-4f60-8a9b-0c1d2e3f4a5b",
+                Some(StatusEvent::ConnectorId("7f1c2b3a-4d5e-4f60-8a9b-0c1d2e3f4a5b".to_string())),
+            ),
+            // Shutdown noise, suppressed by the reader only while we're stopping the process
+            (
+                "2024-05-01T10:05:00Z ERR failed to serve tunnel connection error=\"context canceled\" connIndex=0 event=0 ip=198.41.200.13",
+                Some(StatusEvent::Canceled { fatal: false }),
+            ),
+            // Deprecation notices, even when they mention errors or connections
+            (
+                "2024-05-01T10:00:00Z WRN The --region flag is deprecated and will be removed; connections use the region from your account",
+                Some(StatusEvent::Deprecated("The --region flag is deprecated and will be removed; connections use the region from your account".to_string())),
+            ),
+            // Flag this build doesn't know
+            (
+                "Incorrect Usage: flag provided but not defined: -post-quantum",
+                Some(StatusEvent::UnsupportedFlag("post-quantum".to_string())),
+            ),
+            // Connected without a registration line
+            ("2024-05-01T10:00:01Z INF Initial protocol quic", Some(StatusEvent::Connected)),
+            ("2024-05-01T10:00:01Z INF Start Websocket listener host=localhost:2222", Some(StatusEvent::Connected)),
+            // Unstructured output
+            ("Thank you for trying Cloudflare Tunnel.", None),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(&classify_line(line), expected, "line: {}", line);
+        }
+    }
+
+    #[test]
+    fn exit_code_descriptions() {
+        assert_eq!(describe_exit_code(1), "cloudflared exited: fatal error, see the log above (code 1)");
+        assert_eq!(
+            describe_exit_code(137),
+            "cloudflared was killed by a signal (SIGKILL, signal 9) - often the system running out of memory"
+        );
+        assert_eq!(describe_exit_code(143), "cloudflared was killed by a signal (SIGTERM, signal 15)");
+        assert_eq!(describe_exit_code(42), "cloudflared exited with unrecognised exit code 42");
+    }
+
+    #[test]
+    fn process_sample_parsing() {
+        let stat = "4242 (cloudflared (v2)) S 1 4242 4242 0 -1 4194560 5123 0 0 0 250 50 0 0 20 0 12 0 987654 1300000000 6000 18446744073709551615";
+        let status = "Name:\tcloudflared\nVmPeak:\t  130000 kB\nVmRSS:\t   24000 kB\nThreads:\t12\n";
+        assert_eq!(
+            parse_proc_sample(stat, status),
+            Some(ProcessSample { cpu_secs: 3.0, rss_bytes: 24000 * 1024, started: "987654".to_string() })
+        );
+        assert_eq!(parse_proc_sample("4242 (cloudflared) Z", status), None);
+
+        assert_eq!(parse_ps_time("0:01.50"), Some(1.5));
+        assert_eq!(parse_ps_time("1:02:03"), Some(3723.0));
+        assert_eq!(parse_ps_time("2-00:00:01"), Some(172_801.0));
+    }
+
+    #[test]
+    fn flapping_collapses_into_one_unstable_update() {
+        let update = |status: &str| CloudflareStatusUpdate {
+            status: status.to_string(),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut flaps = FlapDebouncer::default();
+
+        assert_eq!(flaps.observe(&update("connected"), at(0)), FlapDecision::Emit);
+        assert_eq!(flaps.observe(&update("reconnecting"), at(300)), FlapDecision::Emit);
+        assert_eq!(flaps.observe(&update("connected"), at(600)), FlapDecision::Unstable(2));
+        assert_eq!(flaps.observe(&update("reconnecting"), at(900)), FlapDecision::Hold);
+        assert_eq!(flaps.observe(&update("connected"), at(1200)), FlapDecision::Hold);
+        // Warnings aren't transitions and still go out
+        assert_eq!(flaps.observe(&update("warning"), at(1300)), FlapDecision::Emit);
+
+        assert!(flaps.settle(at(2000)).is_none());
+        let settled = flaps.settle(at(3300)).expect("held update released");
+        assert_eq!(settled.status, "connected");
+        assert_eq!(settled.flap_count, Some(4));
+
+        // A slow transition afterwards is emitted straight away
+        assert_eq!(flaps.observe(&update("reconnecting"), at(9000)), FlapDecision::Emit);
+    }
+
+    #[test]
+    fn default_settings_fill_only_unset_values() {
+        let defaults = DefaultTunnelSettings {
+            protocol: Some("http2".to_string()),
+            log_level: Some("debug".to_string()),
+            edge_ip_version: Some("4".to_string()),
+            env: HashMap::from([("TUNNEL_RETRIES".to_string(), "8".to_string())]),
+            ..Default::default()
+        };
+        let mut config = CloudflareConfig::new("app".to_string(), String::new(), 8080);
+        config.env.insert("TUNNEL_TRANSPORT_PROTOCOL".to_string(), "quic".to_string());
+        config.edge_ip_version = Some("6".to_string());
+        defaults.apply(&mut config);
+
+        assert_eq!(config.env.get("TUNNEL_TRANSPORT_PROTOCOL").map(String::as_str), Some("quic"));
+        assert_eq!(config.env.get("TUNNEL_LOGLEVEL").map(String::as_str), Some("debug"));
+        assert_eq!(config.env.get("TUNNEL_RETRIES").map(String::as_str), Some("8"));
+        assert!(!config.env.contains_key("TUNNEL_REGION"));
+        assert_eq!(config.edge_ip_version.as_deref(), Some("6"));
+
+        let mut invalid = defaults.clone();
+        invalid.log_level = Some("warn".to_string());
+        assert!(invalid.validate().is_err());
+        assert!(defaults.validate().is_ok());
+    }
+
+    #[test]
+    fn log_filter_by_level_and_connection() {
+        let lines = [
+            "2024-05-01T10:00:00Z INF Registered tunnel connection connIndex=0 location=sjc07",
+            "2024-05-01T10:05:00Z ERR failed to serve tunnel connection connIndex=1",
+            "2024-05-01T10:05:01Z WRN Connection terminated connIndex=1",
+            "Thank you for trying Cloudflare Tunnel.",
+        ];
+        let matching = |filter: LogFilter| lines.iter().filter(|l| filter.matches(l)).count();
+
+        assert_eq!(matching(LogFilter::default()), 4);
+        assert_eq!(matching(LogFilter { min_level: Some(LogLevel::Warn), conn_index: None }), 2);
+        assert_eq!(matching(LogFilter { min_level: None, conn_index: Some(1) }), 2);
+        assert_eq!(matching(LogFilter { min_level: Some(LogLevel::Error), conn_index: Some(1) }), 1);
+    }
+
+    #[test]
+    fn quality_score_drops_with_disruptions() {
+        assert_eq!(quality_score(false, 0, Some(800), 4, 4), 0);
+        assert_eq!(quality_score(true, 0, Some(800), 4, 4), 100);
+        assert_eq!(quality_score(true, 2, Some(800), 4, 4), 70);
+        // Slow connect and half the edge connections missing
+        assert_eq!(quality_score(true, 0, Some(7000), 2, 4), 80);
+        // No registrations at all (access client) isn't penalised
+        assert_eq!(quality_score(true, 0, None, 0, 4), 100);
+        // Barely holding on, but still ranked above a tunnel that's down
+        assert_eq!(quality_score(true, 10, Some(60_000), 1, 4), 5);
+    }
+
+    /// A registered tunnel with no task behind it
+    fn idle_entry(config: CloudflareConfig, runtime: TunnelRuntime) -> RunningTunnel {
+        RunningTunnel {
+            config,
+            runtime: Arc::new(Mutex::new(runtime)),
+            notify_stop: Arc::new(Notify::new()),
+            notify_reconnect: Arc::new(Notify::new()),
+            notify_config: Arc::new(Notify::new()),
+            handle: tauri::async_runtime::spawn(async {}),
+            generation: 0,
+        }
+    }
+
+    #[test]
+    fn update_config_keeps_a_paused_tunnel_paused() {
+        let manager = CloudflareManager::new();
+        let config = CloudflareConfig::new("paused".to_string(), String::new(), 8000);
+        let runtime = TunnelRuntime { paused: true, ..Default::default() };
+        lock_recover(&manager.tunnels).insert(config.id.clone(), idle_entry(config.clone(), runtime));
+
+        let edited = CloudflareConfig { local_port: 8001, ..config.clone() };
+        assert_eq!(manager.update_config(Arc::new(StdoutSink), edited), Ok(false));
+
+        let tunnels = lock_recover(&manager.tunnels);
+        let tunnel = &tunnels[&config.id];
+        let runtime = lock_recover(&tunnel.runtime);
+        assert!(runtime.paused && !runtime.process_running);
+        assert_eq!(tunnel.generation, 0, "a new connect replaced the entry");
+        assert_eq!(tunnel.config.local_port, 8001);
+    }
+
+    #[test]
+    fn update_config_applies_supervisor_settings_in_place() {
+        let manager = CloudflareManager::new();
+        let config = CloudflareConfig::new("live".to_string(), String::new(), 8000);
+        let runtime = TunnelRuntime { process_running: true, ..Default::default() };
+        lock_recover(&manager.tunnels).insert(config.id.clone(), idle_entry(config.clone(), runtime));
+
+        let edited = CloudflareConfig {
+            shutdown_grace_period_secs: config.shutdown_grace_period_secs + 10,
+            connect_timeout_secs: config.connect_timeout_secs + 30,
+            restart_on_connect_timeout: !config.restart_on_connect_timeout,
+            auto_reconnect: !config.auto_reconnect,
+            check_port_owner: !config.check_port_owner,
+            confirm_expose: !config.confirm_expose,
+            ..config.clone()
+        };
+        assert!(!config.requires_restart(&edited));
+        assert_eq!(manager.update_config(Arc::new(StdoutSink), edited.clone()), Ok(false));
+
+        let tunnels = lock_recover(&manager.tunnels);
+        assert_eq!(tunnels[&config.id].generation, 0, "a new connect replaced the entry");
+        assert_eq!(tunnels[&config.id].config, edited);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn monitor_wakes_once_per_interval_for_all_tunnels() {
+        use std::future::Future;
+
+        let tunnels: Arc<Mutex<HashMap<String, RunningTunnel>>> = Arc::new(Mutex::new(HashMap::new()));
+        for i in 0..20u16 {
+            let config = CloudflareConfig::new(format!("tunnel {}", i), String::new(), 8000 + i);
+            let runtime = TunnelRuntime { process_running: true, ..Default::default() };
+            lock_recover(&tunnels).insert(config.id.clone(), idle_entry(config, runtime));
+        }
+
+        // Every wakeup of the monitor is one poll of its future
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counted = polls.clone();
+        let mut monitor = Box::pin(monitor_loop(tunnels, Arc::new(StdoutSink)));
+        let task = tokio::spawn(std::future::poll_fn(move |cx| {
+            counted.fetch_add(1, Ordering::SeqCst);
+            monitor.as_mut().poll(cx)
+        }));
+
+        tokio::time::sleep(METRICS_SCRAPE_INTERVAL * 10 + METRICS_SCRAPE_INTERVAL / 2).await;
+        task.abort();
+        // The first poll plus one per elapsed interval, not one per tunnel
+        assert_eq!(polls.load(Ordering::SeqCst), 11);
+    }
 }
@@ -0,0 +1,678 @@
+//! Moving tunnels between the app and the cloudflared CLI: reading credentials files
+//! and config.yml into `CloudflareConfig`s, and writing them back out.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::types::cloudflare::{CloudflareConfig, IngressRule, TunnelProtocol, INGRESS_CATCH_ALL_SERVICE};
+
+/// Port used when an imported tunnel has no http(s) ingress service we can map
+const DEFAULT_IMPORT_PORT: u16 = 8317;
+
+/// `<TunnelID>.json` written by `cloudflared tunnel create`
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CredentialsFile {
+    account_tag: String,
+    tunnel_secret: String,
+    #[serde(rename = "TunnelID")]
+    tunnel_id: String,
+    #[serde(default)]
+    tunnel_name: Option<String>,
+}
+
+/// The handful of config.yml keys that map onto a `CloudflareConfig`
+#[derive(Default)]
+struct LocalConfigFile {
+    tunnel: Option<String>,
+    credentials_file: Option<PathBuf>,
+    /// First http(s) or unix ingress service, e.g. http://localhost:8080
+    service: Option<String>,
+    /// Every `ingress:` rule, in order, catch-all included
+    ingress: Vec<IngressRule>,
+    protocol: Option<String>,
+    metrics: Option<String>,
+    no_autoupdate: Option<bool>,
+    /// From the top-level `originRequest:` block
+    origin_server_name: Option<String>,
+    http_host_header: Option<String>,
+    no_tls_verify: Option<bool>,
+}
+
+/// Which top-level block of config.yml a line belongs to
+enum ConfigSection {
+    Ingress,
+    OriginRequest,
+    Other,
+}
+
+/// Directories cloudflared looks in for config.yml and credentials, in its own order
+fn local_config_dirs() -> Vec<PathBuf> {
+    let mut dirs_found: Vec<PathBuf> = dirs::home_dir()
+        .map(|h| vec![h.join(".cloudflared"), h.join(".cloudflare-warp"), h.join("cloudflare-warp")])
+        .unwrap_or_default();
+    #[cfg(not(windows))]
+    dirs_found.extend([PathBuf::from("/etc/cloudflared"), PathBuf::from("/usr/local/etc/cloudflared")]);
+    dirs_found.retain(|d| d.is_dir());
+    dirs_found
+}
+
+/// A scalar value as written after `key:`. Quoted values are unescaped (the inverse of
+/// `yaml_quote`), plain ones lose any trailing comment.
+fn yaml_value(raw: &str) -> String {
+    let raw = raw.trim();
+    let mut value = String::new();
+    if let Some(rest) = raw.strip_prefix('"') {
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(escaped) => value.push(escaped),
+                    None => {}
+                },
+                c => value.push(c),
+            }
+        }
+    } else if let Some(rest) = raw.strip_prefix('\'') {
+        // A doubled quote is the only escape inside single quotes
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '\'' {
+                value.push(c);
+            } else if chars.next_if_eq(&'\'').is_some() {
+                value.push('\'');
+            } else {
+                break;
+            }
+        }
+    } else {
+        value = raw.split(" #").next().unwrap_or("").trim().to_string();
+    }
+    value
+}
+
+/// Line-based reader for the subset of config.yml we care about: top-level keys, the
+/// hostname and service of each `ingress:` rule, and the top-level `originRequest:`
+/// settings. Anything it doesn't recognise, including per-rule settings, is skipped
+/// rather than treated as an error.
+fn parse_local_config(text: &str) -> LocalConfigFile {
+    let mut config = LocalConfigFile::default();
+    let mut section = ConfigSection::Other;
+    // Column of the `-` that starts an ingress rule, and of the rule's own keys
+    let mut rule_columns: Option<(usize, usize)> = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        let top_level = indent == 0 && !trimmed.starts_with('-');
+        let entry = trimmed.trim_start_matches('-').trim_start();
+        let Some((key, value)) = entry.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        let value = yaml_value(value);
+        if top_level {
+            section = match key {
+                "ingress" => ConfigSection::Ingress,
+                "originRequest" => ConfigSection::OriginRequest,
+                _ => ConfigSection::Other,
+            };
+            match key {
+                "tunnel" => config.tunnel = Some(value),
+                "credentials-file" => config.credentials_file = Some(PathBuf::from(value)),
+                "protocol" => config.protocol = Some(value),
+                "metrics" => config.metrics = Some(value),
+                "no-autoupdate" => config.no_autoupdate = Some(value == "true"),
+                _ => {}
+            }
+            continue;
+        }
+        match section {
+            ConfigSection::Ingress => {
+                let key_column = line.trim_end().len() - entry.len();
+                let starts_rule = trimmed.starts_with('-')
+                    && match rule_columns {
+                        Some((dash, _)) => dash == indent,
+                        None => true,
+                    };
+                let rule_key = match rule_columns {
+                    Some((_, column)) => column == key_column,
+                    None => false,
+                };
+                if starts_rule {
+                    rule_columns = Some((indent, key_column));
+                    config.ingress.push(IngressRule { hostname: None, service: String::new() });
+                } else if !rule_key {
+                    // Nested under a rule, e.g. its own originRequest or path
+                    continue;
+                }
+                let Some(rule) = config.ingress.last_mut() else {
+                    continue;
+                };
+                match key {
+                    "hostname" => rule.hostname = Some(value).filter(|h| !h.is_empty()),
+                    "service" => rule.service = value,
+                    _ => {}
+                }
+            }
+            ConfigSection::OriginRequest => match key {
+                "originServerName" => config.origin_server_name = Some(value).filter(|n| !n.is_empty()),
+                "httpHostHeader" => config.http_host_header = Some(value).filter(|h| !h.is_empty()),
+                "noTLSVerify" => config.no_tls_verify = Some(value == "true"),
+                _ => {}
+            },
+            ConfigSection::Other => {}
+        }
+    }
+    config.service = config
+        .ingress
+        .iter()
+        .map(|rule| &rule.service)
+        .find(|s| s.starts_with("http://") || s.starts_with("https://") || s.starts_with("unix:"))
+        .cloned();
+    config
+}
+
+/// A credentials file, or None when it can't be read or isn't one. Any other JSON file
+/// in the directory is tried too, so the tunnel ID must be a real UUID.
+fn read_credentials(path: &Path) -> Option<CredentialsFile> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let creds = serde_json::from_str::<CredentialsFile>(&text).ok()?;
+    uuid::Uuid::parse_str(&creds.tunnel_id).is_ok().then_some(creds)
+}
+
+/// Build the connector token cloudflared accepts with `--token` from a credentials file
+fn credentials_to_token(creds: &CredentialsFile) -> String {
+    let json = serde_json::json!({
+        "a": creds.account_tag,
+        "t": creds.tunnel_id,
+        "s": creds.tunnel_secret,
+    });
+    base64_encode(json.to_string().as_bytes())
+}
+
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard padded base64, enough for building tunnel tokens
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_CHARS[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decode standard or URL-safe base64, padded or not
+fn base64_decode(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut bits: u32 = 0;
+    for c in input.trim_end_matches('=').bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+/// Inverse of `credentials_to_token`
+fn token_to_credentials(token: &str) -> Option<CredentialsFile> {
+    decode_token(token).ok()
+}
+
+/// Decode a connector token, saying what's wrong with it in terms of what the user
+/// pasted: usually a truncated copy or the wrong field from the dashboard
+fn decode_token(token: &str) -> Result<CredentialsFile, String> {
+    #[derive(Deserialize)]
+    struct TokenPayload {
+        #[serde(default)]
+        a: String,
+        #[serde(default)]
+        t: String,
+        #[serde(default)]
+        s: String,
+    }
+    let token = token.trim();
+    if token.is_empty() {
+        return Err("Paste a tunnel token first".to_string());
+    }
+    let bytes = base64_decode(token)
+        .ok_or_else(|| "This isn't a tunnel token - it has characters a token never contains".to_string())?;
+    let payload: TokenPayload = serde_json::from_slice(&bytes).map_err(|_| {
+        "The token doesn't decode - it's probably cut off. Copy the whole token again from the dashboard".to_string()
+    })?;
+    if payload.a.is_empty() || payload.t.is_empty() || payload.s.is_empty() {
+        return Err("The token is missing its account, tunnel or secret - copy it again from the dashboard".to_string());
+    }
+    if uuid::Uuid::parse_str(&payload.t).is_err() {
+        return Err(format!("The token's tunnel ID '{}' isn't a valid UUID", payload.t));
+    }
+    Ok(CredentialsFile {
+        account_tag: payload.a,
+        tunnel_secret: payload.s,
+        tunnel_id: payload.t,
+        tunnel_name: None,
+    })
+}
+
+/// The account and tunnel a connector token belongs to, as (account_id, tunnel_id)
+pub fn token_target(token: &str) -> Result<(String, String), String> {
+    decode_token(token).map(|c| (c.account_tag, c.tunnel_id))
+}
+
+/// Double-quoted YAML scalar, so hostnames and services with `:` or `#` stay intact
+fn yaml_quote(value: &str) -> String {
+    format!("\"{}\"", value.trim().replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The `ingress:` block for `rules`, in order
+fn ingress_yaml(rules: &[IngressRule]) -> String {
+    let mut yaml = String::from("ingress:\n");
+    for rule in rules {
+        match rule.hostname.as_deref().map(str::trim).filter(|h| !h.is_empty()) {
+            Some(hostname) => {
+                yaml.push_str(&format!("  - hostname: {}\n", yaml_quote(hostname)));
+                yaml.push_str(&format!("    service: {}\n", yaml_quote(&rule.service)));
+            }
+            None => yaml.push_str(&format!("  - service: {}\n", yaml_quote(&rule.service))),
+        }
+    }
+    yaml
+}
+
+/// Top-level `originRequest:` defaults applied to every ingress rule; empty when the
+/// config sets none
+fn origin_request_yaml(config: &CloudflareConfig) -> String {
+    let mut settings = Vec::new();
+    if let Some(name) = config.origin_server_name.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+        settings.push(format!("  originServerName: {}\n", yaml_quote(name)));
+    }
+    if let Some(host) = config.http_host_header.as_deref().map(str::trim).filter(|h| !h.is_empty()) {
+        settings.push(format!("  httpHostHeader: {}\n", yaml_quote(host)));
+    }
+    if config.no_tls_verify {
+        settings.push("  noTLSVerify: true\n".to_string());
+    }
+    if settings.is_empty() {
+        return String::new();
+    }
+    format!("originRequest:\n{}", settings.concat())
+}
+
+/// Where `write_ingress_config` puts a tunnel's generated config. Pure, so building the
+/// command line doesn't touch the disk.
+pub fn generated_config_path(id: &str) -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("proxypal")
+        .join("cloudflared")
+        .join(format!("{}.yml", id))
+}
+
+/// Write the config cloudflared runs with for a named tunnel with local ingress rules.
+/// Credentials still come from the token on the command line.
+pub fn write_ingress_config(config: &CloudflareConfig) -> Result<PathBuf, String> {
+    let path = generated_config_path(&config.id);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create '{}': {}", dir.display(), e))?;
+    }
+    let yaml = format!(
+        "# Generated by ProxyPal for {}\n{}{}",
+        config.name,
+        origin_request_yaml(config),
+        ingress_yaml(&config.ingress_rules())
+    );
+    std::fs::write(&path, yaml)
+        .map_err(|e| format!("Failed to write tunnel config '{}': {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Turn each tunnel credentials file found locally into a config ready to save.
+/// config.yml in the same directory supplies the origin, ingress rules and protocol
+/// when it refers to that tunnel. Unreadable or malformed files are skipped.
+pub fn import_local_tunnels() -> Vec<CloudflareConfig> {
+    let mut seen_tunnels: Vec<String> = Vec::new();
+    local_config_dirs()
+        .iter()
+        .flat_map(|dir| import_dir(dir, &mut seen_tunnels))
+        .collect()
+}
+
+/// The tunnels whose credentials are in `dir`, skipping any already in `seen_tunnels`
+fn import_dir(dir: &Path, seen_tunnels: &mut Vec<String>) -> Vec<CloudflareConfig> {
+    let mut imported: Vec<CloudflareConfig> = Vec::new();
+    let local_config = ["config.yml", "config.yaml"]
+        .iter()
+        .find_map(|name| std::fs::read_to_string(dir.join(name)).ok())
+        .map(|text| parse_local_config(&text))
+        .unwrap_or_default();
+
+    let mut credential_paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(path) = &local_config.credentials_file {
+        if !credential_paths.contains(path) {
+            credential_paths.push(path.clone());
+        }
+    }
+
+    for path in credential_paths {
+        let Some(creds) = read_credentials(&path) else {
+            continue;
+        };
+        if seen_tunnels.contains(&creds.tunnel_id) {
+            continue;
+        }
+        seen_tunnels.push(creds.tunnel_id.clone());
+
+        let matches_config = local_config.credentials_file.as_deref() == Some(path.as_path())
+            || local_config.tunnel.as_deref() == Some(creds.tunnel_id.as_str())
+            || (creds.tunnel_name.is_some() && local_config.tunnel == creds.tunnel_name);
+
+        let name = creds
+            .tunnel_name
+            .clone()
+            .or_else(|| {
+                local_config
+                    .tunnel
+                    .clone()
+                    .filter(|t| matches_config && *t != creds.tunnel_id)
+            })
+            .unwrap_or_else(|| format!("Imported {}", &creds.tunnel_id[..creds.tunnel_id.len().min(8)]));
+
+        let mut config = CloudflareConfig::new(name, credentials_to_token(&creds), DEFAULT_IMPORT_PORT);
+        if matches_config {
+            if let Some(socket) = local_config.service.as_deref().and_then(|s| s.strip_prefix("unix:")) {
+                config.origin_protocol = TunnelProtocol::Unix;
+                config.unix_socket = Some(socket.to_string());
+            } else if let Some(service) = local_config.service.as_deref().and_then(|s| url::Url::parse(s).ok()) {
+                if service.scheme() == "https" {
+                    config.origin_protocol = TunnelProtocol::Https;
+                }
+                if let Some(host) = service.host_str() {
+                    config.local_host = host.to_string();
+                }
+                if let Some(port) = service.port_or_known_default() {
+                    config.local_port = port;
+                }
+            }
+            if let Some(protocol) = &local_config.protocol {
+                config.env.insert("TUNNEL_TRANSPORT_PROTOCOL".to_string(), protocol.clone());
+            }
+            config.metrics_addr = local_config.metrics.clone();
+            // The file already binds it that way, so importing keeps that choice
+            config.metrics_expose = config.metrics_exposed();
+            if let Some(no_autoupdate) = local_config.no_autoupdate {
+                config.no_autoupdate = no_autoupdate;
+            }
+            // A single hostname-less rule is just the origin above; anything routed by
+            // hostname is kept as rules, minus the catch-all `ingress_rules` adds back
+            if local_config.ingress.iter().any(|rule| rule.hostname.is_some()) {
+                let mut rules = local_config.ingress.clone();
+                let generated_catch_all = rules.len() >= 2
+                    && rules[rules.len() - 2].hostname.is_some()
+                    && rules.last().is_some_and(|r| r.hostname.is_none() && r.service == INGRESS_CATCH_ALL_SERVICE);
+                if generated_catch_all {
+                    rules.pop();
+                }
+                config.ingress = rules;
+            }
+            config.origin_server_name = local_config.origin_server_name.clone();
+            config.http_host_header = local_config.http_host_header.clone();
+            if let Some(no_tls_verify) = local_config.no_tls_verify {
+                config.no_tls_verify = no_tls_verify;
+            }
+        }
+        imported.push(config);
+    }
+    imported
+}
+
+/// Write a file only its owner can read on Unix, as `cloudflared tunnel create` does
+/// for the credentials it holds the tunnel secret in
+fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    // The mode only applies to new files; an earlier export may have left a wider one
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(data)
+}
+
+/// Write `config` as a cloudflared config.yml at `path`, with the tunnel's credentials
+/// in `<TunnelID>.json` next to it, so `cloudflared tunnel --config <path> run` serves
+/// the same origin. Importing the result gives back the same origin, ingress rules and
+/// `originRequest` settings.
+pub fn export_config(config: &CloudflareConfig, path: &Path) -> Result<(), String> {
+    if config.tunnel_token.is_empty() {
+        return Err("Quick tunnels have no credentials to export - only named tunnels can run from a config file".to_string());
+    }
+    let mut creds = token_to_credentials(&config.tunnel_token)
+        .ok_or_else(|| "Couldn't decode the tunnel token into credentials".to_string())?;
+    creds.tunnel_name = Some(config.name.clone());
+
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let credentials_path = dir.join(format!("{}.json", creds.tunnel_id));
+    let credentials_json = serde_json::to_string_pretty(&creds)
+        .map_err(|e| format!("Failed to serialize credentials: {}", e))?;
+    write_private(&credentials_path, credentials_json.as_bytes())
+        .map_err(|e| format!("Failed to write credentials to '{}': {}", credentials_path.display(), e))?;
+
+    let mut yaml = String::new();
+    yaml.push_str(&format!("# Exported from ProxyPal: {}\n", config.name));
+    yaml.push_str(&format!("tunnel: {}\n", creds.tunnel_id));
+    yaml.push_str(&format!("credentials-file: {}\n", yaml_quote(&credentials_path.display().to_string())));
+    if let Some(protocol) = config.env.get("TUNNEL_TRANSPORT_PROTOCOL") {
+        yaml.push_str(&format!("protocol: {}\n", protocol));
+    }
+    if let Some(metrics) = config.metrics_addr.as_deref().filter(|m| !m.trim().is_empty()) {
+        yaml.push_str(&format!("metrics: {}\n", metrics.trim()));
+    }
+    yaml.push_str(&format!("no-autoupdate: {}\n", config.no_autoupdate));
+    yaml.push_str(&origin_request_yaml(config));
+    if config.ingress.is_empty() {
+        yaml.push_str("ingress:\n");
+        yaml.push_str(&format!("  - service: {}\n", config.origin_url()));
+    } else {
+        yaml.push_str(&ingress_yaml(&config.ingress_rules()));
+    }
+
+    std::fs::write(path, yaml)
+        .map_err(|e| format!("Failed to write config to '{}': {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TUNNEL_ID: &str = "6ff42ae2-765d-4adf-8112-31c55c1551ef";
+
+    fn credentials() -> CredentialsFile {
+        CredentialsFile {
+            account_tag: "0123456789abcdef".to_string(),
+            tunnel_secret: "c2VjcmV0LXNlY3JldC1zZWNyZXQ=".to_string(),
+            tunnel_id: TUNNEL_ID.to_string(),
+            tunnel_name: None,
+        }
+    }
+
+    #[test]
+    fn base64_round_trip_and_variants() {
+        let inputs: [&[u8]; 6] = [b"", b"a", b"ab", b"abc", b"abcd", b"abcde"];
+        for data in inputs {
+            assert_eq!(base64_decode(&base64_encode(data)).as_deref(), Some(data));
+        }
+        // Standard and URL-safe alphabets, with or without padding
+        assert_eq!(base64_decode("+/8="), Some(vec![0xfb, 0xff]));
+        assert_eq!(base64_decode("-_8"), Some(vec![0xfb, 0xff]));
+        assert_eq!(base64_decode("ab$c"), None);
+    }
+
+    #[test]
+    fn decode_token_cases() {
+        let token = credentials_to_token(&credentials());
+        let decoded = decode_token(&format!("  {}\n", token)).unwrap();
+        assert_eq!(decoded.account_tag, "0123456789abcdef");
+        assert_eq!(decoded.tunnel_id, TUNNEL_ID);
+        assert_eq!(decoded.tunnel_secret, "c2VjcmV0LXNlY3JldC1zZWNyZXQ=");
+
+        let missing_secret = base64_encode(format!(r#"{{"a":"acct","t":"{}"}}"#, TUNNEL_ID).as_bytes());
+        let bad_uuid = base64_encode(br#"{"a":"acct","t":"not-a-uuid","s":"secret"}"#);
+        let cases: &[(&str, &str)] = &[
+            ("", "Paste a tunnel token"),
+            ("not a token!", "characters a token never contains"),
+            (&token[..20], "cut off"),
+            (missing_secret.as_str(), "missing its account, tunnel or secret"),
+            (bad_uuid.as_str(), "isn't a valid UUID"),
+        ];
+        for (input, expected) in cases {
+            let err = decode_token(input).err().unwrap_or_default();
+            assert!(err.contains(expected), "{:?}: {}", input, err);
+        }
+    }
+
+    #[test]
+    fn yaml_value_unquotes_and_strips_comments() {
+        let cases = [
+            (" plain # comment", "plain"),
+            (r#" "C:\\Users\\me\\a #b.json" # comment"#, r"C:\Users\me\a #b.json"),
+            (r#" "say \"hi\"""#, r#"say "hi""#),
+            (" 'it''s' ", "it's"),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(yaml_value(raw), expected, "{:?}", raw);
+        }
+        assert_eq!(yaml_value(&yaml_quote(r#"a\b"c"#)), r#"a\b"c"#);
+    }
+
+    #[test]
+    fn read_credentials_requires_uuid_tunnel_id() {
+        let dir = std::env::temp_dir().join(format!("proxypal creds {}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, tunnel_id: &str| {
+            let path = dir.join(name);
+            let json = serde_json::json!({ "AccountTag": "acct", "TunnelSecret": "secret", "TunnelID": tunnel_id });
+            std::fs::write(&path, json.to_string()).unwrap();
+            path
+        };
+        let valid = read_credentials(&write("valid.json", TUNNEL_ID)).map(|c| c.tunnel_id);
+        let multibyte = read_credentials(&write("multibyte.json", "トンネルトンネル")).is_some();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(valid.as_deref(), Some(TUNNEL_ID));
+        assert!(!multibyte);
+    }
+
+    #[test]
+    fn export_then_import_round_trip() {
+        // " #" in the directory name would end an unquoted YAML value early
+        let dir = std::env::temp_dir().join(format!("proxypal export #{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut config = CloudflareConfig::new("Staging API".to_string(), credentials_to_token(&credentials()), 9443);
+        config.local_host = "10.0.0.5".to_string();
+        config.origin_protocol = TunnelProtocol::Https;
+        config.metrics_addr = Some("127.0.0.1:2000".to_string());
+        config.no_autoupdate = false;
+        config.env.insert("TUNNEL_TRANSPORT_PROTOCOL".to_string(), "http2".to_string());
+        config.ingress = vec![
+            IngressRule { hostname: Some("api.example.com".to_string()), service: config.origin_url() },
+            IngressRule { hostname: Some("docs.example.com".to_string()), service: "http://localhost:3000".to_string() },
+        ];
+        config.origin_server_name = Some("origin.internal".to_string());
+        config.http_host_header = Some("api.example.com".to_string());
+        config.no_tls_verify = true;
+
+        let result = export_config(&config, &dir.join("config.yml"));
+        let imported = import_dir(&dir, &mut Vec::new());
+        let _ = std::fs::remove_dir_all(&dir);
+        result.unwrap();
+
+        let [tunnel] = imported.as_slice() else {
+            panic!("expected one imported tunnel, got {}", imported.len());
+        };
+        assert_eq!(tunnel.name, config.name);
+        assert_eq!(tunnel.tunnel_token, config.tunnel_token);
+        assert_eq!(tunnel.origin_url(), config.origin_url());
+        assert_eq!(tunnel.metrics_addr, config.metrics_addr);
+        assert_eq!(tunnel.no_autoupdate, config.no_autoupdate);
+        assert_eq!(tunnel.env, config.env);
+        assert_eq!(tunnel.ingress, config.ingress);
+        assert_eq!(tunnel.origin_server_name, config.origin_server_name);
+        assert_eq!(tunnel.http_host_header, config.http_host_header);
+        assert_eq!(tunnel.no_tls_verify, config.no_tls_verify);
+    }
+
+    #[test]
+    fn parse_local_config_reads_ingress_and_origin_request() {
+        let text = [
+            "tunnel: 6ff42ae2-765d-4adf-8112-31c55c1551ef",
+            "originRequest:",
+            "  noTLSVerify: true",
+            "  originServerName: origin.internal # shared by every rule",
+            "ingress:",
+            "  - hostname: app.example.com",
+            "    service: http://localhost:8080",
+            "    originRequest:",
+            "      httpHostHeader: ignored.example.com",
+            "  - service: http_status:404",
+            "metrics: 127.0.0.1:2000",
+        ]
+        .join("\n");
+        let parsed = parse_local_config(&text);
+        assert_eq!(
+            parsed.ingress,
+            vec![
+                IngressRule { hostname: Some("app.example.com".to_string()), service: "http://localhost:8080".to_string() },
+                IngressRule { hostname: None, service: "http_status:404".to_string() },
+            ]
+        );
+        assert_eq!(parsed.service.as_deref(), Some("http://localhost:8080"));
+        assert_eq!(parsed.no_tls_verify, Some(true));
+        assert_eq!(parsed.origin_server_name.as_deref(), Some("origin.internal"));
+        assert_eq!(parsed.http_host_header, None);
+        assert_eq!(parsed.metrics.as_deref(), Some("127.0.0.1:2000"));
+    }
+}
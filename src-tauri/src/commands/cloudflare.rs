@@ -1,7 +1,14 @@
+use std::sync::Arc;
 use tauri::{AppHandle, State, command};
-use crate::cloudflare_manager::CloudflareManager;
+use crate::cloudflare_api;
+use crate::cloudflared_config;
+use crate::tunnel_service;
+use crate::cloudflare_manager::{
+    self, CloudflareManager, CloudflareTunnelStatus, ConfigTestReport, LogFilter, LogLevel, OrphanSweep, TunnelTestResult,
+    SHUTDOWN_TIMEOUT,
+};
 use crate::config::{save_config_to_file, load_config};
-use crate::types::cloudflare::CloudflareConfig;
+use crate::types::cloudflare::{AccessConfig, CloudflareConfig, DefaultTunnelSettings, DnsProxyConfig};
 
 #[command]
 pub async fn get_cloudflare_configs() -> Result<Vec<CloudflareConfig>, String> {
@@ -10,15 +17,55 @@ pub async fn get_cloudflare_configs() -> Result<Vec<CloudflareConfig>, String> {
 }
 
 #[command]
-pub async fn save_cloudflare_config(cf_config: CloudflareConfig) -> Result<Vec<CloudflareConfig>, String> {
+pub async fn save_cloudflare_config(
+    app: AppHandle,
+    state: State<'_, CloudflareManager>,
+    mut cf_config: CloudflareConfig
+) -> Result<Vec<CloudflareConfig>, String> {
+    cf_config.normalize();
+    cf_config.validate()?;
+
     let mut current_config = load_config();
     
     if let Some(idx) = current_config.cloudflare_configs.iter().position(|c| c.id == cf_config.id) {
-        current_config.cloudflare_configs[idx] = cf_config;
+        current_config.cloudflare_configs[idx] = cf_config.clone();
     } else {
-        current_config.cloudflare_configs.push(cf_config);
+        current_config.cloudflare_configs.push(cf_config.clone());
     }
     
+    save_config_to_file(&current_config)?;
+
+    // Keep a running tunnel in sync; trivial edits don't restart cloudflared
+    state.update_config(Arc::new(app), cf_config)?;
+
+    Ok(current_config.cloudflare_configs)
+}
+
+/// Saved tunnel configs. They live in config.json next to the rest of the app
+/// settings, which is written atomically (temp file + rename).
+#[command]
+pub async fn load_cloudflare_configs() -> Result<Vec<CloudflareConfig>, String> {
+    Ok(load_config().cloudflare_configs)
+}
+
+/// Replace the whole saved tunnel list, e.g. after a reorder or bulk import. Every
+/// config is validated before anything is written; running tunnels that are no
+/// longer in the list are stopped.
+#[command]
+pub async fn save_cloudflare_configs(
+    state: State<'_, CloudflareManager>,
+    mut cf_configs: Vec<CloudflareConfig>
+) -> Result<Vec<CloudflareConfig>, String> {
+    for cf_config in cf_configs.iter_mut() {
+        cf_config.normalize();
+        cf_config.validate().map_err(|e| format!("{}: {}", cf_config.name, e))?;
+    }
+
+    let mut current_config = load_config();
+    for removed in current_config.cloudflare_configs.iter().filter(|c| !cf_configs.iter().any(|n| n.id == c.id)) {
+        state.disconnect(&removed.id);
+    }
+    current_config.cloudflare_configs = cf_configs;
     save_config_to_file(&current_config)?;
     Ok(current_config.cloudflare_configs)
 }
@@ -35,13 +82,15 @@ pub async fn delete_cloudflare_config(_app: AppHandle, state: State<'_, Cloudfla
     Ok(current_config.cloudflare_configs)
 }
 
+/// Enable or disable a tunnel. Returns false when disabling a tunnel that wasn't
+/// running, so the UI doesn't report a stop that didn't happen.
 #[command]
 pub async fn set_cloudflare_connection(
     app: AppHandle,
     state: State<'_, CloudflareManager>,
     id: String,
     enable: bool
-) -> Result<(), String> {
+) -> Result<bool, String> {
     let mut config = load_config();
     if let Some(c) = config.cloudflare_configs.iter_mut().find(|c| c.id == id) {
         c.enabled = enable;
@@ -51,12 +100,356 @@ pub async fn set_cloudflare_connection(
         save_config_to_file(&config)?;
         
         if enable {
-            state.connect(app, target_config);
+            state.connect(Arc::new(app), target_config)?;
+            Ok(true)
         } else {
-            state.disconnect(&id);
+            Ok(state.disconnect(&id))
         }
-        Ok(())
     } else {
         Err("Config not found".to_string())
     }
 }
+
+#[command]
+pub async fn get_cloudflare_status(state: State<'_, CloudflareManager>, id: String) -> Result<CloudflareTunnelStatus, String> {
+    Ok(state.get_status(&id))
+}
+
+/// The URL to copy for a running tunnel, from the backend rather than the log view
+#[command]
+pub async fn get_tunnel_url(state: State<'_, CloudflareManager>, id: String) -> Result<String, String> {
+    state.tunnel_url(&id)
+}
+
+#[command]
+pub async fn reconnect_cloudflare_tunnel(state: State<'_, CloudflareManager>, id: String) -> Result<(), String> {
+    if state.reconnect(&id) {
+        Ok(())
+    } else {
+        Err("Tunnel is not running".to_string())
+    }
+}
+
+#[command]
+pub async fn list_cloudflare_tunnels(state: State<'_, CloudflareManager>) -> Result<Vec<CloudflareTunnelStatus>, String> {
+    Ok(state.list_tunnels())
+}
+
+#[command]
+pub async fn list_tunnels_by_tag(state: State<'_, CloudflareManager>, tag: String) -> Result<Vec<CloudflareTunnelStatus>, String> {
+    if tag.trim().is_empty() {
+        return Err("Tag must not be empty".to_string());
+    }
+    let config = load_config();
+    Ok(state.list_tunnels_by_tag(&config.cloudflare_configs, &tag))
+}
+
+#[command]
+pub async fn get_cloudflare_connect_times(state: State<'_, CloudflareManager>, id: String) -> Result<Vec<u64>, String> {
+    let state = state.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || state.connect_times(&id))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Recent cloudflared output, optionally only lines at `min_level` or above and/or
+/// from one edge connection
+#[command]
+pub async fn get_cloudflare_logs(
+    state: State<'_, CloudflareManager>,
+    id: String,
+    limit: Option<usize>,
+    min_level: Option<LogLevel>,
+    conn_index: Option<u32>,
+) -> Result<Vec<String>, String> {
+    Ok(state.get_logs(&id, limit.unwrap_or(200), LogFilter { min_level, conn_index }))
+}
+
+#[command]
+pub async fn export_cloudflare_diagnostics(state: State<'_, CloudflareManager>, id: String, path: String) -> Result<String, String> {
+    let saved_config = load_config().cloudflare_configs.into_iter().find(|c| c.id == id);
+    let diagnostics = state.diagnostics(&id, saved_config).await;
+
+    let data = serde_json::to_string_pretty(&diagnostics)
+        .map_err(|e| format!("Failed to serialize diagnostics: {}", e))?;
+    let target = path.clone();
+    tauri::async_runtime::spawn_blocking(move || std::fs::write(&target, data))
+        .await
+        .map_err(|e| format!("Failed to export diagnostics: {}", e))?
+        .map_err(|e| format!("Failed to write diagnostics to '{}': {}", path, e))?;
+    Ok(path)
+}
+
+#[command]
+pub async fn pause_cloudflare_tunnel(state: State<'_, CloudflareManager>, id: String) -> Result<(), String> {
+    if state.pause(&id) {
+        Ok(())
+    } else {
+        Err("Tunnel is not running".to_string())
+    }
+}
+
+#[command]
+pub async fn resume_cloudflare_tunnel(app: AppHandle, state: State<'_, CloudflareManager>, id: String) -> Result<(), String> {
+    state.resume(Arc::new(app), &id)
+}
+
+#[command]
+pub async fn stop_all_cloudflare_tunnels(state: State<'_, CloudflareManager>) -> Result<(), String> {
+    state.stop_all(SHUTDOWN_TIMEOUT).await;
+    Ok(())
+}
+
+/// Stop stray cloudflared processes left by crashed runs. Without `confirm` this only
+/// lists them, so the UI can ask first.
+#[command]
+pub async fn kill_orphaned_cloudflared(state: State<'_, CloudflareManager>, confirm: bool) -> Result<OrphanSweep, String> {
+    Ok(state.kill_orphans(confirm).await)
+}
+
+/// Change how many tunnels may run at once; running tunnels are left alone
+#[command]
+pub async fn set_cloudflare_max_concurrent(state: State<'_, CloudflareManager>, limit: usize) -> Result<(), String> {
+    if limit == 0 {
+        return Err("The tunnel limit must be at least 1".to_string());
+    }
+    let mut current_config = load_config();
+    current_config.cloudflare_max_concurrent = limit;
+    save_config_to_file(&current_config)?;
+    state.set_max_concurrent(limit);
+    Ok(())
+}
+
+#[command]
+pub async fn get_cloudflare_default_settings() -> Result<DefaultTunnelSettings, String> {
+    Ok(load_config().cloudflare_default_settings)
+}
+
+/// Save the settings every tunnel inherits; running tunnels keep theirs until restarted
+#[command]
+pub async fn set_cloudflare_default_settings(
+    state: State<'_, CloudflareManager>,
+    defaults: DefaultTunnelSettings,
+) -> Result<DefaultTunnelSettings, String> {
+    defaults.validate()?;
+    let mut current_config = load_config();
+    current_config.cloudflare_default_settings = defaults;
+    save_config_to_file(&current_config)?;
+    state.set_default_settings(current_config.cloudflare_default_settings.clone());
+    Ok(current_config.cloudflare_default_settings)
+}
+
+/// Create a named tunnel through the Cloudflare API and save it as a new config.
+/// The API token is only used for this call and is never stored.
+#[command]
+pub async fn create_cloudflare_tunnel(
+    name: String,
+    account_id: String,
+    api_token: String,
+    local_port: u16,
+) -> Result<CloudflareConfig, String> {
+    let tunnel = cloudflare_api::create_tunnel(&name, &account_id, &api_token).await?;
+
+    let cf_config = CloudflareConfig::new(tunnel.name, tunnel.token, local_port);
+    cf_config.validate()?;
+
+    let mut current_config = load_config();
+    current_config.cloudflare_configs.push(cf_config.clone());
+    save_config_to_file(&current_config)?;
+    Ok(cf_config)
+}
+
+#[command]
+pub async fn route_cloudflare_dns(
+    tunnel_id: String,
+    hostname: String,
+    api_token: String,
+    overwrite: Option<bool>,
+) -> Result<(), String> {
+    cloudflare_api::route_dns(&tunnel_id, &hostname, &api_token, overwrite.unwrap_or(false)).await
+}
+
+/// Existing tunnels on the account, so the UI can offer a picker instead of a token field
+#[command]
+pub async fn list_account_tunnels(account_id: String, api_token: String) -> Result<Vec<cloudflare_api::AccountTunnel>, String> {
+    cloudflare_api::list_account_tunnels(&account_id, &api_token).await
+}
+
+/// Connector token for a tunnel picked from list_account_tunnels. Not stored; the UI
+/// saves it with the config.
+#[command]
+pub async fn get_account_tunnel_token(account_id: String, tunnel_id: String, api_token: String) -> Result<String, String> {
+    cloudflare_api::get_tunnel_token(&account_id, &tunnel_id, &api_token).await
+}
+
+/// Check a pasted tunnel token before saving it. Without an API token this only
+/// decodes it; with one it also confirms the tunnel exists and returns its name.
+#[command]
+pub async fn validate_token(token: String, api_token: Option<String>) -> Result<cloudflare_api::TokenInfo, String> {
+    cloudflare_api::validate_token(&token, api_token.as_deref()).await
+}
+
+/// Public hostnames configured for a named tunnel in the dashboard
+#[command]
+pub async fn get_tunnel_hostnames(tunnel_id: String, api_token: String) -> Result<Vec<String>, String> {
+    cloudflare_api::get_tunnel_hostnames(&tunnel_id, &api_token).await
+}
+
+/// Register `proxypal tunnel run` with the OS (systemd user unit, launchd agent or
+/// logon task) so saved tunnels keep running without the GUI. Returns where it went.
+#[command]
+pub async fn install_cloudflare_background_service() -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(tunnel_service::install)
+        .await
+        .map_err(|e| format!("Failed to install background service: {}", e))?
+}
+
+#[command]
+pub async fn uninstall_cloudflare_background_service() -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(tunnel_service::uninstall)
+        .await
+        .map_err(|e| format!("Failed to remove background service: {}", e))?
+}
+
+/// Tunnels found in the local cloudflared config directory that aren't saved yet.
+/// Nothing is stored; the UI adds the ones the user picks via save_cloudflare_config.
+#[command]
+pub async fn import_local_tunnels() -> Result<Vec<CloudflareConfig>, String> {
+    let found = tauri::async_runtime::spawn_blocking(cloudflared_config::import_local_tunnels)
+        .await
+        .map_err(|e| format!("Failed to scan cloudflared config: {}", e))?;
+    let saved = load_config().cloudflare_configs;
+    Ok(found
+        .into_iter()
+        .filter(|c| !saved.iter().any(|s| s.tunnel_token == c.tunnel_token))
+        .collect())
+}
+
+/// Write a saved config out as a cloudflared config.yml (plus credentials file) at `path`.
+/// The credentials file holds the tunnel secret, so on Unix it's created readable by the
+/// owner only (0600).
+#[command]
+pub async fn export_cloudflare_config(id: String, path: String) -> Result<String, String> {
+    let cf_config = load_config()
+        .cloudflare_configs
+        .into_iter()
+        .find(|c| c.id == id)
+        .ok_or_else(|| "Config not found".to_string())?;
+    let target = std::path::PathBuf::from(&path);
+    tauri::async_runtime::spawn_blocking(move || cloudflared_config::export_config(&cf_config, &target))
+        .await
+        .map_err(|e| format!("Failed to export config: {}", e))??;
+    Ok(path)
+}
+
+#[command]
+pub async fn get_cloudflare_access_configs() -> Result<Vec<AccessConfig>, String> {
+    Ok(load_config().cloudflare_access_configs)
+}
+
+#[command]
+pub async fn save_cloudflare_access_config(access_config: AccessConfig) -> Result<Vec<AccessConfig>, String> {
+    access_config.validate()?;
+
+    let mut current_config = load_config();
+    if let Some(idx) = current_config.cloudflare_access_configs.iter().position(|c| c.id == access_config.id) {
+        current_config.cloudflare_access_configs[idx] = access_config;
+    } else {
+        current_config.cloudflare_access_configs.push(access_config);
+    }
+    save_config_to_file(&current_config)?;
+    Ok(current_config.cloudflare_access_configs)
+}
+
+#[command]
+pub async fn delete_cloudflare_access_config(state: State<'_, CloudflareManager>, id: String) -> Result<Vec<AccessConfig>, String> {
+    let mut current_config = load_config();
+    state.disconnect(&id);
+    current_config.cloudflare_access_configs.retain(|c| c.id != id);
+    save_config_to_file(&current_config)?;
+    Ok(current_config.cloudflare_access_configs)
+}
+
+#[command]
+pub async fn set_cloudflare_access_connection(
+    app: AppHandle,
+    state: State<'_, CloudflareManager>,
+    id: String,
+    enable: bool
+) -> Result<bool, String> {
+    let mut config = load_config();
+    let Some(access) = config.cloudflare_access_configs.iter_mut().find(|c| c.id == id) else {
+        return Err("Access config not found".to_string());
+    };
+    access.enabled = enable;
+    let target = access.clone();
+    save_config_to_file(&config)?;
+
+    if enable {
+        state.connect_access(Arc::new(app), target).map(|_| true)
+    } else {
+        Ok(state.disconnect(&id))
+    }
+}
+
+#[command]
+pub async fn get_cloudflare_dns_proxy_configs() -> Result<Vec<DnsProxyConfig>, String> {
+    Ok(load_config().cloudflare_dns_proxy_configs)
+}
+
+#[command]
+pub async fn save_cloudflare_dns_proxy_config(dns_config: DnsProxyConfig) -> Result<Vec<DnsProxyConfig>, String> {
+    dns_config.validate()?;
+
+    let mut current_config = load_config();
+    if let Some(idx) = current_config.cloudflare_dns_proxy_configs.iter().position(|c| c.id == dns_config.id) {
+        current_config.cloudflare_dns_proxy_configs[idx] = dns_config;
+    } else {
+        current_config.cloudflare_dns_proxy_configs.push(dns_config);
+    }
+    save_config_to_file(&current_config)?;
+    Ok(current_config.cloudflare_dns_proxy_configs)
+}
+
+#[command]
+pub async fn delete_cloudflare_dns_proxy_config(state: State<'_, CloudflareManager>, id: String) -> Result<Vec<DnsProxyConfig>, String> {
+    let mut current_config = load_config();
+    state.disconnect(&id);
+    current_config.cloudflare_dns_proxy_configs.retain(|c| c.id != id);
+    save_config_to_file(&current_config)?;
+    Ok(current_config.cloudflare_dns_proxy_configs)
+}
+
+#[command]
+pub async fn set_cloudflare_dns_proxy_connection(
+    app: AppHandle,
+    state: State<'_, CloudflareManager>,
+    id: String,
+    enable: bool
+) -> Result<bool, String> {
+    let mut config = load_config();
+    let Some(dns) = config.cloudflare_dns_proxy_configs.iter_mut().find(|c| c.id == id) else {
+        return Err("DNS proxy config not found".to_string());
+    };
+    dns.enabled = enable;
+    let target = dns.clone();
+    save_config_to_file(&config)?;
+
+    if enable {
+        state.start_dns_proxy(Arc::new(app), target).map(|_| true)
+    } else {
+        Ok(state.disconnect(&id))
+    }
+}
+
+/// Check a config works (binary, token, edge reachability) without keeping it running
+#[command]
+pub async fn test_cloudflare_config(cf_config: CloudflareConfig) -> Result<TunnelTestResult, String> {
+    Ok(cloudflare_manager::test_config(cf_config).await)
+}
+
+/// Test every saved tunnel without keeping any of them running
+#[command]
+pub async fn test_all_configs(state: State<'_, CloudflareManager>) -> Result<Vec<ConfigTestReport>, String> {
+    Ok(state.test_all(load_config().cloudflare_configs).await)
+}
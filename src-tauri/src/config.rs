@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 use crate::types::{
-    amp::generate_uuid, cloudflare::CloudflareConfig, AmpModelMapping, AmpOpenAIProvider,
+    amp::generate_uuid, cloudflare::{AccessConfig, CloudflareConfig, DefaultTunnelSettings, DnsProxyConfig}, AmpModelMapping, AmpOpenAIProvider,
     ClaudeApiKey, CodexApiKey, CopilotConfig, GeminiApiKey, SshConfig, VertexApiKey,
 };
 
@@ -88,10 +88,36 @@ pub struct AppConfig {
     pub ssh_configs: Vec<SshConfig>,
     #[serde(default)]
     pub cloudflare_configs: Vec<CloudflareConfig>,
+    #[serde(default)]
+    pub cloudflare_access_configs: Vec<AccessConfig>,
+    #[serde(default)]
+    pub cloudflare_dns_proxy_configs: Vec<DnsProxyConfig>,
+    /// Serve Prometheus metrics for all tunnels at cloudflare_metrics_bind/metrics
+    #[serde(default)]
+    pub cloudflare_metrics_enabled: bool,
+    #[serde(default = "default_cloudflare_metrics_bind")]
+    pub cloudflare_metrics_bind: String,
+    /// Keep the app resident while tunnels run after the last window closes
+    #[serde(default)]
+    pub cloudflare_keep_running: bool,
+    /// Most Cloudflare tunnels that may run at once
+    #[serde(default = "default_cloudflare_max_concurrent")]
+    pub cloudflare_max_concurrent: usize,
+    /// Merged into every Cloudflare tunnel when it starts
+    #[serde(default)]
+    pub cloudflare_default_settings: DefaultTunnelSettings,
     #[serde(default = "default_disable_control_panel")]
     pub disable_control_panel: bool,
 }
 
+fn default_cloudflare_max_concurrent() -> usize {
+    crate::cloudflare_manager::DEFAULT_MAX_CONCURRENT_TUNNELS
+}
+
+fn default_cloudflare_metrics_bind() -> String {
+    "127.0.0.1:9464".to_string()
+}
+
 fn default_disable_control_panel() -> bool {
     true
 }
@@ -172,6 +198,13 @@ impl Default for AppConfig {
             ws_auth: false,
             ssh_configs: Vec::new(),
             cloudflare_configs: Vec::new(),
+            cloudflare_access_configs: Vec::new(),
+            cloudflare_dns_proxy_configs: Vec::new(),
+            cloudflare_metrics_enabled: false,
+            cloudflare_metrics_bind: default_cloudflare_metrics_bind(),
+            cloudflare_keep_running: false,
+            cloudflare_max_concurrent: default_cloudflare_max_concurrent(),
+            cloudflare_default_settings: DefaultTunnelSettings::default(),
             disable_control_panel: true,
         }
     }
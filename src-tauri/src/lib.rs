@@ -6,6 +6,10 @@ mod types;
 mod utils;
 mod ssh_manager;
 mod cloudflare_manager;
+mod cloudflare_api;
+mod cloudflared_config;
+mod tunnel_cli;
+mod tunnel_service;
 
 use crate::config::{get_aggregate_path, get_auth_path, get_history_path, load_config, save_config_to_file};
 use crate::state::AppState;
@@ -34,6 +38,11 @@ use tauri::{
 use tauri_plugin_opener::OpenerExt;
 use tauri_plugin_shell::ShellExt;
 
+/// Headless `proxypal tunnel ...` subcommand; returns the exit code
+pub fn run_tunnel_cli(args: &[String]) -> i32 {
+    tunnel_cli::run(args)
+}
+
 /// Get management key from config (used for internal proxy API calls)
 fn get_management_key() -> String {
     load_config().management_key
@@ -6877,6 +6886,27 @@ async fn set_close_to_tray(state: State<'_, AppState>, enabled: bool) -> Result<
     Ok(())
 }
 
+// Keep Cloudflare tunnels running after the window closes
+#[tauri::command]
+async fn get_cloudflare_keep_running(state: State<'_, AppState>) -> Result<bool, String> {
+    let config = state.config.lock().unwrap();
+    Ok(config.cloudflare_keep_running)
+}
+
+#[tauri::command]
+async fn set_cloudflare_keep_running(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    {
+        let mut config = state.config.lock().unwrap();
+        config.cloudflare_keep_running = enabled;
+    }
+    let config_to_save = {
+        let config = state.config.lock().unwrap();
+        config.clone()
+    };
+    crate::commands::config::save_config(state, config_to_save)?;
+    Ok(())
+}
+
 // OpenAI-Compatible Providers
 #[tauri::command]
 async fn get_openai_compatible_providers(state: State<'_, AppState>) -> Result<Vec<OpenAICompatibleProvider>, String> {
@@ -7927,10 +7957,41 @@ pub fn run() {
             tauri::async_runtime::spawn(async move {
                 let config = crate::config::load_config();
                 let cf_manager = app_handle.state::<CloudflareManager>();
-                for cf_config in config.cloudflare_configs {
-                    if cf_config.enabled {
-                        println!("[Cloudflare] Auto-starting tunnel: {}", cf_config.name);
-                        cf_manager.connect(app_handle.clone(), cf_config);
+                cf_manager.set_max_concurrent(config.cloudflare_max_concurrent);
+                cf_manager.set_default_settings(config.cloudflare_default_settings.clone());
+                if config.cloudflare_metrics_enabled {
+                    cf_manager.start_metrics_server(config.cloudflare_metrics_bind.clone());
+                }
+                let to_start: Vec<_> = config
+                    .cloudflare_configs
+                    .into_iter()
+                    // Scheduled tunnels are started by the scheduler when their window opens
+                    .filter(|c| (c.enabled || c.auto_start) && c.schedule.is_none())
+                    .inspect(|c| println!("[Cloudflare] Auto-starting tunnel: {}", c.name))
+                    .collect();
+                let results = cf_manager
+                    .connect_many(Arc::new(app_handle.clone()), to_start, std::time::Duration::from_millis(500))
+                    .await;
+                for result in results.iter().filter(|r| !r.accepted) {
+                    eprintln!(
+                        "[Cloudflare] Failed to auto-start tunnel {}: {}",
+                        result.id,
+                        result.error.as_deref().unwrap_or("unknown error")
+                    );
+                }
+                cf_manager.start_scheduler(Arc::new(app_handle.clone()));
+
+                for access in config.cloudflare_access_configs.into_iter().filter(|c| c.enabled) {
+                    println!("[Cloudflare] Auto-starting access client: {}", access.name);
+                    if let Err(e) = cf_manager.connect_access(Arc::new(app_handle.clone()), access) {
+                        eprintln!("[Cloudflare] Failed to auto-start access client: {}", e);
+                    }
+                }
+
+                for dns in config.cloudflare_dns_proxy_configs.into_iter().filter(|c| c.enabled) {
+                    println!("[Cloudflare] Auto-starting DNS proxy: {}", dns.name);
+                    if let Err(e) = cf_manager.start_dns_proxy(Arc::new(app_handle.clone()), dns) {
+                        eprintln!("[Cloudflare] Failed to auto-start DNS proxy: {}", e);
                     }
                 }
             });
@@ -8056,6 +8117,8 @@ pub fn run() {
             // Window behavior
             get_close_to_tray,
             set_close_to_tray,
+            get_cloudflare_keep_running,
+            set_cloudflare_keep_running,
             // Claude Code Settings
             get_claude_code_settings,
             set_claude_code_model,
@@ -8071,6 +8134,43 @@ pub fn run() {
             commands::cloudflare::save_cloudflare_config,
             commands::cloudflare::delete_cloudflare_config,
             commands::cloudflare::set_cloudflare_connection,
+            commands::cloudflare::get_cloudflare_status,
+            commands::cloudflare::reconnect_cloudflare_tunnel,
+            commands::cloudflare::list_cloudflare_tunnels,
+            commands::cloudflare::list_tunnels_by_tag,
+            commands::cloudflare::get_tunnel_url,
+            commands::cloudflare::kill_orphaned_cloudflared,
+            commands::cloudflare::get_cloudflare_connect_times,
+            commands::cloudflare::validate_token,
+            commands::cloudflare::get_cloudflare_logs,
+            commands::cloudflare::export_cloudflare_diagnostics,
+            commands::cloudflare::pause_cloudflare_tunnel,
+            commands::cloudflare::resume_cloudflare_tunnel,
+            commands::cloudflare::stop_all_cloudflare_tunnels,
+            commands::cloudflare::set_cloudflare_max_concurrent,
+            commands::cloudflare::get_cloudflare_default_settings,
+            commands::cloudflare::set_cloudflare_default_settings,
+            commands::cloudflare::create_cloudflare_tunnel,
+            commands::cloudflare::route_cloudflare_dns,
+            commands::cloudflare::get_tunnel_hostnames,
+            commands::cloudflare::list_account_tunnels,
+            commands::cloudflare::get_account_tunnel_token,
+            commands::cloudflare::install_cloudflare_background_service,
+            commands::cloudflare::uninstall_cloudflare_background_service,
+            commands::cloudflare::import_local_tunnels,
+            commands::cloudflare::export_cloudflare_config,
+            commands::cloudflare::get_cloudflare_access_configs,
+            commands::cloudflare::save_cloudflare_access_config,
+            commands::cloudflare::delete_cloudflare_access_config,
+            commands::cloudflare::set_cloudflare_access_connection,
+            commands::cloudflare::get_cloudflare_dns_proxy_configs,
+            commands::cloudflare::save_cloudflare_dns_proxy_config,
+            commands::cloudflare::delete_cloudflare_dns_proxy_config,
+            commands::cloudflare::set_cloudflare_dns_proxy_connection,
+            commands::cloudflare::test_cloudflare_config,
+            commands::cloudflare::test_all_configs,
+            commands::cloudflare::load_cloudflare_configs,
+            commands::cloudflare::save_cloudflare_configs,
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
@@ -8085,12 +8185,20 @@ pub fn run() {
                     if label == "main" {
                         if let tauri::WindowEvent::CloseRequested { api, .. } = win_event {
                             // Check if close_to_tray is enabled
-                            let close_to_tray = app_handle
+                            let (close_to_tray, keep_tunnels) = app_handle
                                 .try_state::<AppState>()
-                                .map(|state| state.config.lock().unwrap().close_to_tray)
-                                .unwrap_or(true);
+                                .map(|state| {
+                                    let config = state.config.lock().unwrap();
+                                    (config.close_to_tray, config.cloudflare_keep_running)
+                                })
+                                .unwrap_or((true, false));
+                            // Running tunnels outlive the window when the user asked for it
+                            let keep_tunnels = keep_tunnels
+                                && app_handle
+                                    .try_state::<CloudflareManager>()
+                                    .is_some_and(|cf_manager| cf_manager.running_count() > 0);
                             
-                            if close_to_tray {
+                            if close_to_tray || keep_tunnels {
                                 // Hide to tray instead of closing
                                 if let Some(window) = app_handle.get_webview_window("main") {
                                     println!("[ProxyPal] Hiding to system tray...");
@@ -8128,6 +8236,13 @@ pub fn run() {
                     if let Some(ssh_manager) = app_handle.try_state::<SshManager>() {
                         ssh_manager.disconnect_all();
                     }
+
+                    // Stop Cloudflare tunnels and wait until every cloudflared is reaped
+                    if let Some(cf_manager) = app_handle.try_state::<CloudflareManager>() {
+                        tauri::async_runtime::block_on(
+                            cf_manager.shutdown(cloudflare_manager::SHUTDOWN_TIMEOUT),
+                        );
+                    }
                 }
                 _ => {}
             }
@@ -2,5 +2,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("tunnel") {
+        std::process::exit(proxypal_lib::run_tunnel_cli(&args[1..]));
+    }
     proxypal_lib::run()
 }
@@ -0,0 +1,139 @@
+//! Headless entry point: `proxypal tunnel run [--config tunnels.json]` runs tunnels
+//! with the same engine as the GUI and prints their events to stdout.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cloudflare_manager::{CloudflareManager, StatusSink, StdoutSink, SHUTDOWN_TIMEOUT};
+use crate::types::cloudflare::CloudflareConfig;
+
+const USAGE: &str = "Usage: proxypal tunnel run [--config <tunnels.json>]
+
+Starts Cloudflare tunnels without the GUI and logs their events to stdout.
+Without --config, the tunnels saved in the app's config are run as the app would:
+enabled and auto-start tunnels now, scheduled ones by their schedule, plus the
+enabled access clients and DNS proxies.
+The file may be a JSON array of tunnel configs or a ProxyPal config.json; its
+tunnels are all started straight away.";
+
+/// Configs from `path`: either a bare array or an app config with `cloudflareConfigs`
+fn load_tunnels_file(path: &str) -> Result<Vec<CloudflareConfig>, String> {
+    let data = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+    let value: serde_json::Value = serde_json::from_str(&data)
+        .map_err(|e| format!("Failed to parse '{}': {}", path, e))?;
+    let configs = match value {
+        serde_json::Value::Object(mut map) => map
+            .remove("cloudflareConfigs")
+            .ok_or_else(|| format!("'{}' has no cloudflareConfigs", path))?,
+        array => array,
+    };
+    serde_json::from_value(configs).map_err(|e| format!("Invalid tunnel config in '{}': {}", path, e))
+}
+
+/// Wait for Ctrl+C, or SIGTERM on unix, which is how systemd and launchd stop the
+/// background runner
+async fn wait_for_stop_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            return;
+        }
+    }
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        eprintln!("[Cloudflare] Failed to wait for Ctrl+C: {}", e);
+    }
+}
+
+/// Run the `tunnel` subcommand and return the process exit code
+pub fn run(args: &[String]) -> i32 {
+    let config_path = match args {
+        [cmd] if cmd == "run" => None,
+        [cmd, flag, path] if cmd == "run" && flag == "--config" => Some(path.clone()),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    let app_config = crate::config::load_config();
+    let (configs, access, dns, scheduled) = match &config_path {
+        Some(path) => match load_tunnels_file(path) {
+            Ok(configs) => (configs, Vec::new(), Vec::new(), false),
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        },
+        // Same selection as the app's auto-start: scheduled tunnels are left to the scheduler
+        None => (
+            app_config
+                .cloudflare_configs
+                .iter()
+                .filter(|c| (c.enabled || c.auto_start) && c.schedule.is_none())
+                .cloned()
+                .collect(),
+            app_config.cloudflare_access_configs.iter().filter(|c| c.enabled).cloned().collect(),
+            app_config.cloudflare_dns_proxy_configs.iter().filter(|c| c.enabled).cloned().collect(),
+            app_config.cloudflare_configs.iter().any(|c| c.schedule.is_some()),
+        ),
+    };
+    if configs.is_empty() && access.is_empty() && dns.is_empty() && !scheduled {
+        eprintln!("No tunnels to start");
+        return 1;
+    }
+
+    tauri::async_runtime::block_on(async move {
+        let manager = CloudflareManager::new();
+        manager.set_max_concurrent(app_config.cloudflare_max_concurrent);
+        manager.set_default_settings(app_config.cloudflare_default_settings.clone());
+        if app_config.cloudflare_metrics_enabled {
+            manager.start_metrics_server(app_config.cloudflare_metrics_bind.clone());
+        }
+
+        let sink: Arc<dyn StatusSink> = Arc::new(StdoutSink);
+        let results = manager
+            .connect_many(sink.clone(), configs, Duration::from_millis(500))
+            .await;
+        for result in results.iter().filter(|r| !r.accepted) {
+            eprintln!(
+                "[Cloudflare] Failed to start tunnel {} ({}): {}",
+                result.name,
+                result.id,
+                result.error.as_deref().unwrap_or("unknown error")
+            );
+        }
+        let mut running = results.iter().any(|r| r.accepted);
+
+        if scheduled {
+            manager.start_scheduler(sink.clone());
+            running = true;
+        }
+        for access in access {
+            println!("[Cloudflare] Starting access client: {}", access.name);
+            match manager.connect_access(sink.clone(), access) {
+                Ok(()) => running = true,
+                Err(e) => eprintln!("[Cloudflare] Failed to start access client: {}", e),
+            }
+        }
+        for dns in dns {
+            println!("[Cloudflare] Starting DNS proxy: {}", dns.name);
+            match manager.start_dns_proxy(sink.clone(), dns) {
+                Ok(()) => running = true,
+                Err(e) => eprintln!("[Cloudflare] Failed to start DNS proxy: {}", e),
+            }
+        }
+        if !running {
+            return 1;
+        }
+
+        wait_for_stop_signal().await;
+        manager.shutdown(SHUTDOWN_TIMEOUT).await;
+        0
+    })
+}
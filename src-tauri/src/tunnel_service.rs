@@ -0,0 +1,191 @@
+//! Registering the headless `proxypal tunnel run` engine with the OS so tunnels run
+//! without the GUI and start with the session: a systemd user unit on Linux, a launchd
+//! agent on macOS and a logon scheduled task on Windows. See docs/cloudflare-background.md.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+#[cfg(target_os = "linux")]
+const SYSTEMD_UNIT: &str = "proxypal-tunnels.service";
+#[cfg(target_os = "macos")]
+const LAUNCHD_LABEL: &str = "com.proxypal.tunnels";
+#[cfg(windows)]
+const TASK_NAME: &str = "ProxyPal Tunnels";
+
+fn current_exe() -> Result<PathBuf, String> {
+    std::env::current_exe().map_err(|e| format!("Failed to locate the ProxyPal executable: {}", e))
+}
+
+/// Run a service-manager command, turning a non-zero exit into its stderr
+fn run_tool(cmd: &mut Command) -> Result<(), String> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+    let program = cmd.get_program().to_string_lossy().to_string();
+    let output = cmd
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let detail = if stderr.trim().is_empty() { stdout.trim() } else { stderr.trim() };
+    Err(format!("{} failed: {}", program, detail))
+}
+
+#[cfg(target_os = "linux")]
+fn unit_path() -> Result<PathBuf, String> {
+    dirs::config_dir()
+        .map(|d| d.join("systemd").join("user").join(SYSTEMD_UNIT))
+        .ok_or_else(|| "Could not find the user config directory".to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn plist_path() -> Result<PathBuf, String> {
+    dirs::home_dir()
+        .map(|h| h.join("Library").join("LaunchAgents").join(format!("{}.plist", LAUNCHD_LABEL)))
+        .ok_or_else(|| "Could not find the home directory".to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Install and start the background runner for the current user. Returns where it was
+/// registered so the UI can show it.
+#[cfg(target_os = "linux")]
+pub fn install() -> Result<String, String> {
+    let exe = current_exe()?;
+    let path = unit_path()?;
+    let unit = format!(
+        "[Unit]\n\
+         Description=ProxyPal Cloudflare tunnels\n\
+         After=network-online.target\n\
+         Wants=network-online.target\n\
+         \n\
+         [Service]\n\
+         ExecStart=\"{}\" tunnel run\n\
+         Restart=on-failure\n\
+         RestartSec=5\n\
+         TimeoutStopSec=20\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        exe.display()
+    );
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create '{}': {}", dir.display(), e))?;
+    }
+    std::fs::write(&path, unit)
+        .map_err(|e| format!("Failed to write '{}': {}", path.display(), e))?;
+    run_tool(Command::new("systemctl").args(["--user", "daemon-reload"]))?;
+    run_tool(Command::new("systemctl").args(["--user", "enable", "--now", SYSTEMD_UNIT]))?;
+    Ok(path.display().to_string())
+}
+
+#[cfg(target_os = "linux")]
+pub fn uninstall() -> Result<(), String> {
+    let path = unit_path()?;
+    if !path.exists() {
+        return Ok(());
+    }
+    // Already stopped or disabled by hand is fine; the unit file still goes
+    let _ = run_tool(Command::new("systemctl").args(["--user", "disable", "--now", SYSTEMD_UNIT]));
+    std::fs::remove_file(&path)
+        .map_err(|e| format!("Failed to remove '{}': {}", path.display(), e))?;
+    run_tool(Command::new("systemctl").args(["--user", "daemon-reload"]))
+}
+
+#[cfg(target_os = "macos")]
+pub fn install() -> Result<String, String> {
+    let exe = current_exe()?;
+    let path = plist_path()?;
+    let log_path = dirs::home_dir()
+        .map(|h| h.join("Library").join("Logs").join("proxypal-tunnels.log"))
+        .ok_or_else(|| "Could not find the home directory".to_string())?;
+    let log = xml_escape(&log_path.display().to_string());
+    let plist = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{exe}</string>
+        <string>tunnel</string>
+        <string>run</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+        label = LAUNCHD_LABEL,
+        exe = xml_escape(&exe.display().to_string()),
+        log = log,
+    );
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create '{}': {}", dir.display(), e))?;
+    }
+    // Reinstalling over a loaded agent needs it unloaded first
+    let _ = run_tool(Command::new("launchctl").arg("unload").arg(&path));
+    std::fs::write(&path, plist)
+        .map_err(|e| format!("Failed to write '{}': {}", path.display(), e))?;
+    run_tool(Command::new("launchctl").args(["load", "-w"]).arg(&path))?;
+    Ok(path.display().to_string())
+}
+
+#[cfg(target_os = "macos")]
+pub fn uninstall() -> Result<(), String> {
+    let path = plist_path()?;
+    if !path.exists() {
+        return Ok(());
+    }
+    let _ = run_tool(Command::new("launchctl").args(["unload", "-w"]).arg(&path));
+    std::fs::remove_file(&path)
+        .map_err(|e| format!("Failed to remove '{}': {}", path.display(), e))
+}
+
+/// A real Windows service has to answer the Service Control Manager, which the plain
+/// `tunnel run` binary doesn't; a task that starts at logon needs no admin rights and
+/// runs the same engine.
+#[cfg(windows)]
+pub fn install() -> Result<String, String> {
+    let exe = current_exe()?;
+    let action = format!("\"{}\" tunnel run", exe.display());
+    run_tool(Command::new("schtasks").args(["/Create", "/F", "/TN", TASK_NAME, "/SC", "ONLOGON", "/RL", "LIMITED", "/TR"]).arg(&action))?;
+    run_tool(Command::new("schtasks").args(["/Run", "/TN", TASK_NAME]))?;
+    Ok(format!("Task Scheduler: {}", TASK_NAME))
+}
+
+#[cfg(windows)]
+pub fn uninstall() -> Result<(), String> {
+    if run_tool(Command::new("schtasks").args(["/Query", "/TN", TASK_NAME])).is_err() {
+        return Ok(());
+    }
+    // Not running is fine; deletion is what matters
+    let _ = run_tool(Command::new("schtasks").args(["/End", "/TN", TASK_NAME]));
+    run_tool(Command::new("schtasks").args(["/Delete", "/F", "/TN", TASK_NAME]))
+}
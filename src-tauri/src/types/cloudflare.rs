@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -9,6 +10,768 @@ pub struct CloudflareConfig {
     pub tunnel_token: String,
     /// Local port to expose (usually 8317)
     pub local_port: u16,
+    /// Local host the origin listens on. "localhost" is sent to cloudflared as 127.0.0.1,
+    /// since it may resolve to ::1 first; use "::1" (or "[::1]") for an IPv6-only origin.
+    #[serde(default = "default_local_host")]
+    pub local_host: String,
     #[serde(default)]
     pub enabled: bool,
+    /// Start on app launch even if the tunnel was off when the app last quit
+    #[serde(default)]
+    pub auto_start: bool,
+    /// Address for cloudflared's Prometheus metrics server (e.g. 127.0.0.1:49312)
+    #[serde(default)]
+    pub metrics_addr: Option<String>,
+    /// Allow `metrics_addr` to bind an interface other machines can reach. The
+    /// endpoint also serves diagnostics, so this must be chosen explicitly.
+    #[serde(default)]
+    pub metrics_expose: bool,
+    /// Pass --post-quantum to require post-quantum key agreement on edge connections
+    #[serde(default)]
+    pub post_quantum: bool,
+    /// Pass --no-autoupdate so cloudflared never self-updates mid-tunnel
+    #[serde(default = "default_no_autoupdate")]
+    pub no_autoupdate: bool,
+    /// Seconds to wait for cloudflared to drain after a graceful stop before force-killing it
+    #[serde(default = "default_shutdown_grace_period_secs")]
+    pub shutdown_grace_period_secs: u64,
+    /// Warn when a started process hasn't connected after this many seconds; 0 disables
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    /// On a connect timeout, kill the process and go through the retry path instead of
+    /// only warning and waiting
+    #[serde(default)]
+    pub restart_on_connect_timeout: bool,
+    /// cloudflared's own drain window on SIGINT (`--grace-period`); its default is 30s
+    #[serde(default)]
+    pub grace_period_secs: Option<u64>,
+    /// Stop a quick tunnel after this many seconds without a request. Traffic is read
+    /// from cloudflared's metrics, so `metrics_addr` must be set. Off when None.
+    #[serde(default)]
+    pub idle_timeout_secs: Option<u64>,
+    /// Allow a quick tunnel to publish a port that usually carries a sensitive service
+    /// (SSH, RDP, databases...). Without it such a tunnel refuses to start.
+    #[serde(default)]
+    pub confirm_expose: bool,
+    /// Probe the local port before starting and warn if nothing is listening
+    #[serde(default = "default_check_local_port")]
+    pub check_local_port: bool,
+    /// For quick tunnels, report which process already holds the local port at start
+    #[serde(default)]
+    pub check_port_owner: bool,
+    /// Respawn cloudflared after it exits or fails to start; when false the tunnel stays down
+    #[serde(default = "default_auto_reconnect")]
+    pub auto_reconnect: bool,
+    /// Append cloudflared output to a size-rotated log file under the app's logs dir
+    #[serde(default)]
+    pub log_to_file: bool,
+    /// Extra arguments appended verbatim after the built-in ones. Not validated -
+    /// an escape hatch for cloudflared flags without first-class fields.
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+    /// Environment variables set on the cloudflared process (e.g. TUNNEL_TRANSPORT_PROTOCOL)
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Outbound proxy cloudflared uses to reach the edge (e.g. http://proxy.corp:3128)
+    #[serde(default)]
+    pub egress_proxy: Option<String>,
+    /// Use the OS proxy settings as the egress proxy when `egress_proxy` is empty.
+    /// Re-detected before every spawn, so switching networks is picked up on reconnect.
+    #[serde(default)]
+    pub use_system_proxy: bool,
+    /// Stream cloudflared output to the frontend as batched `cloudflare-log` events
+    #[serde(default)]
+    pub stream_logs: bool,
+    /// Keep the tunnel up only inside this time window; the scheduler starts and stops it
+    #[serde(default)]
+    pub schedule: Option<TunnelSchedule>,
+    /// URL that receives each status update as a JSON POST (e.g. a Slack or PagerDuty hook)
+    #[serde(default)]
+    pub status_webhook: Option<String>,
+    /// Probe Cloudflare's edge over TCP and UDP before each start and report the result
+    #[serde(default)]
+    pub edge_preflight: bool,
+    /// Address family for edge connections: "4", "6" or "auto" (cloudflared's default)
+    #[serde(default)]
+    pub edge_ip_version: Option<String>,
+    /// How a quick tunnel reaches the origin. Named tunnels route per the dashboard's
+    /// ingress; for them this only drives the startup origin check and config export.
+    #[serde(default)]
+    pub origin_protocol: TunnelProtocol,
+    /// Socket path for `TunnelProtocol::Unix`, e.g. /var/run/app.sock
+    #[serde(default)]
+    pub unix_socket: Option<String>,
+    /// Hostname cloudflared sends as SNI and checks the origin's certificate against,
+    /// for HTTPS origins whose cert isn't issued for the local host
+    #[serde(default)]
+    pub origin_server_name: Option<String>,
+    /// Host header cloudflared sends to the origin instead of the public hostname, for
+    /// local apps that route by Host
+    #[serde(default)]
+    pub http_host_header: Option<String>,
+    /// Accept any certificate from an HTTPS origin, e.g. a self-signed internal service.
+    /// Traffic to the origin can then be intercepted, so it warns on start.
+    #[serde(default)]
+    pub no_tls_verify: bool,
+    /// Local ingress for a named tunnel managed from a config file rather than the
+    /// dashboard. When set, cloudflared runs with a generated config holding these
+    /// rules; a catch-all is appended if the last rule has a hostname.
+    #[serde(default)]
+    pub ingress: Vec<IngressRule>,
+    /// Free-form labels for grouping tunnels, e.g. by project
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// External cloudflared config file, passed as `--config`
+    #[serde(default)]
+    pub config_file: Option<String>,
+    /// Restart cloudflared when `config_file` changes on disk
+    #[serde(default)]
+    pub watch_config_file: bool,
+    /// Directory cloudflared runs in, so relative paths in its config resolve the same
+    /// however the app was launched. A relative `config_file` is taken from here too.
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    /// Set when this config runs a `cloudflared access tcp` client instead of a tunnel.
+    /// Derived from an `AccessConfig` at start, never persisted.
+    #[serde(skip)]
+    pub access: Option<AccessConfig>,
+    /// Set when this config runs `cloudflared proxy-dns` instead of a tunnel. Derived
+    /// from a `DnsProxyConfig` at start, never persisted.
+    #[serde(skip)]
+    pub dns_proxy: Option<DnsProxyConfig>,
+}
+
+/// One cloudflared ingress rule. A rule without a hostname matches every request, so
+/// it can only be the last one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct IngressRule {
+    #[serde(default)]
+    pub hostname: Option<String>,
+    /// e.g. http://localhost:8080, unix:/run/app.sock or http_status:404
+    pub service: String,
+}
+
+/// Terminal rule cloudflared requires when the user's last rule has a hostname
+pub const INGRESS_CATCH_ALL_SERVICE: &str = "http_status:404";
+
+impl IngressRule {
+    fn hostname(&self) -> Option<&str> {
+        self.hostname.as_deref().map(str::trim).filter(|h| !h.is_empty())
+    }
+}
+
+/// Origin scheme for quick tunnels
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TunnelProtocol {
+    /// http://local_host:local_port
+    #[default]
+    Http,
+    /// https://local_host:local_port
+    Https,
+    /// unix:<unix_socket>
+    Unix,
+}
+
+/// Client side of a service behind Cloudflare Access:
+/// `cloudflared access tcp --hostname <hostname> --url <local_bind>`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessConfig {
+    pub id: String,
+    pub name: String,
+    /// Protected hostname, e.g. ssh.example.com
+    pub hostname: String,
+    /// Local address the forwarder listens on, e.g. localhost:2222
+    #[serde(default = "default_access_local_bind")]
+    pub local_bind: String,
+    /// Access service token, for non-interactive auth instead of the browser login
+    #[serde(default)]
+    pub service_token_id: Option<String>,
+    #[serde(default)]
+    pub service_token_secret: Option<String>,
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_auto_reconnect")]
+    pub auto_reconnect: bool,
+}
+
+fn default_access_local_bind() -> String {
+    "localhost:2222".to_string()
+}
+
+impl AccessConfig {
+    /// Split `local_bind` into host and port
+    fn bind_parts(&self) -> Option<(String, u16)> {
+        let (host, port) = self.local_bind.trim().rsplit_once(':')?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        Some((if host.is_empty() { "localhost" } else { host }.to_string(), port.parse().ok()?))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let hostname = self.hostname.trim();
+        if hostname.is_empty() || hostname.contains("://") || hostname.chars().any(char::is_whitespace) {
+            return Err("Access hostname must be a bare hostname like ssh.example.com".to_string());
+        }
+        if self.bind_parts().is_none() {
+            return Err(format!("Invalid local address '{}', expected host:port", self.local_bind));
+        }
+        if self.service_token_id.is_some() != self.service_token_secret.is_some() {
+            return Err("Service token needs both an ID and a secret".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn redacted(&self) -> AccessConfig {
+        let mut config = self.clone();
+        if config.service_token_secret.is_some() {
+            config.service_token_secret = Some("***".to_string());
+        }
+        config
+    }
+
+    /// The tunnel config the manager runs for this client, so access clients get the
+    /// same status, stop and retry handling as tunnels
+    pub fn to_tunnel_config(&self) -> CloudflareConfig {
+        let (local_host, local_port) = self.bind_parts().unwrap_or_else(|| ("localhost".to_string(), 2222));
+        let mut config = CloudflareConfig::new(self.name.clone(), String::new(), local_port);
+        config.id = self.id.clone();
+        config.local_host = local_host;
+        config.enabled = self.enabled;
+        // The local port is ours to listen on, not an origin to probe
+        config.check_local_port = false;
+        config.auto_reconnect = self.auto_reconnect;
+        if let (Some(id), Some(secret)) = (&self.service_token_id, &self.service_token_secret) {
+            config.env.insert("TUNNEL_SERVICE_TOKEN_ID".to_string(), id.clone());
+            config.env.insert("TUNNEL_SERVICE_TOKEN_SECRET".to_string(), secret.clone());
+        }
+        config.access = Some(self.clone());
+        config
+    }
+}
+
+/// Local DNS-over-HTTPS forwarder:
+/// `cloudflared proxy-dns --address <address> --port <port> --upstream <url>...`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsProxyConfig {
+    pub id: String,
+    pub name: String,
+    /// Address the DNS listener binds to
+    #[serde(default = "default_dns_proxy_address")]
+    pub address: String,
+    #[serde(default = "default_dns_proxy_port")]
+    pub port: u16,
+    /// DoH endpoints queries are forwarded to, tried in order
+    #[serde(default = "default_dns_proxy_upstreams")]
+    pub upstreams: Vec<String>,
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_auto_reconnect")]
+    pub auto_reconnect: bool,
+}
+
+fn default_dns_proxy_address() -> String {
+    "localhost".to_string()
+}
+
+// Unprivileged, so binding it doesn't need root; 53 works when run as admin
+fn default_dns_proxy_port() -> u16 {
+    5053
+}
+
+fn default_dns_proxy_upstreams() -> Vec<String> {
+    vec![
+        "https://1.1.1.1/dns-query".to_string(),
+        "https://1.0.0.1/dns-query".to_string(),
+    ]
+}
+
+impl DnsProxyConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let address = self.address.trim();
+        if address.is_empty() || address.contains("://") || address.chars().any(char::is_whitespace) {
+            return Err("DNS listen address must be a bare host or IP like localhost".to_string());
+        }
+        if self.port == 0 {
+            return Err("DNS listen port must be between 1 and 65535".to_string());
+        }
+        let upstreams: Vec<&str> = self.upstreams.iter().map(|u| u.trim()).filter(|u| !u.is_empty()).collect();
+        if upstreams.is_empty() {
+            return Err("Add at least one DNS-over-HTTPS upstream, e.g. https://1.1.1.1/dns-query".to_string());
+        }
+        for upstream in upstreams {
+            match url::Url::parse(upstream) {
+                Ok(url) if url.scheme() == "https" && url.host_str().is_some() => {}
+                _ => return Err(format!("Upstream '{}' must be an https:// DNS-over-HTTPS URL", upstream)),
+            }
+        }
+        Ok(())
+    }
+
+    /// The tunnel config the manager runs for this forwarder, so it shares status, stop
+    /// and retry handling with tunnels
+    pub fn to_tunnel_config(&self) -> CloudflareConfig {
+        let mut config = CloudflareConfig::new(self.name.clone(), String::new(), self.port);
+        config.id = self.id.clone();
+        config.local_host = self.address.trim().to_string();
+        config.enabled = self.enabled;
+        // The port is ours to listen on, not an origin to probe
+        config.check_local_port = false;
+        config.check_port_owner = false;
+        config.auto_reconnect = self.auto_reconnect;
+        config.dns_proxy = Some(self.clone());
+        config
+    }
+}
+
+/// Settings shared by every tunnel, merged in when a tunnel starts. A value set on the
+/// tunnel itself, or the matching variable in its `env`, wins over the default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DefaultTunnelSettings {
+    /// Edge transport: "quic", "http2" or "auto" (TUNNEL_TRANSPORT_PROTOCOL)
+    #[serde(default)]
+    pub protocol: Option<String>,
+    /// Edge region; cloudflared only accepts "us" (TUNNEL_REGION)
+    #[serde(default)]
+    pub region: Option<String>,
+    /// cloudflared log level, "debug" or "info" (TUNNEL_LOGLEVEL)
+    #[serde(default)]
+    pub log_level: Option<String>,
+    /// Address family for edge connections: "4", "6" or "auto"
+    #[serde(default)]
+    pub edge_ip_version: Option<String>,
+    /// Variables set on every cloudflared process
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+impl DefaultTunnelSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(protocol) = non_empty(&self.protocol) {
+            if !matches!(protocol, "quic" | "http2" | "auto") {
+                return Err(format!("Invalid protocol '{}' - use quic, http2 or auto", protocol));
+            }
+        }
+        if let Some(region) = non_empty(&self.region) {
+            if region != "us" {
+                return Err(format!("Invalid region '{}' - cloudflared only supports us", region));
+            }
+        }
+        if let Some(level) = non_empty(&self.log_level) {
+            // Connection events are logged at info; a quieter level hides them and the
+            // tunnel would never show as connected
+            if !matches!(level, "debug" | "info") {
+                return Err(format!("Invalid log level '{}' - use debug or info", level));
+            }
+        }
+        if let Some(version) = non_empty(&self.edge_ip_version) {
+            if !matches!(version, "4" | "6" | "auto") {
+                return Err(format!("Invalid edge IP version '{}' - use 4, 6 or auto", version));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill in whatever `config` leaves unset
+    pub fn apply(&self, config: &mut CloudflareConfig) {
+        let mut env: Vec<(String, String)> = self
+            .env
+            .iter()
+            .filter(|(key, _)| !key.trim().is_empty())
+            .map(|(key, value)| (key.trim().to_string(), value.clone()))
+            .collect();
+        for (key, value) in [
+            ("TUNNEL_TRANSPORT_PROTOCOL", &self.protocol),
+            ("TUNNEL_REGION", &self.region),
+            ("TUNNEL_LOGLEVEL", &self.log_level),
+        ] {
+            let Some(value) = non_empty(value) else { continue };
+            env.push((key.to_string(), value.to_string()));
+        }
+        for (key, value) in env {
+            if !config.env.keys().any(|k| k.trim() == key) {
+                config.env.insert(key, value);
+            }
+        }
+        if non_empty(&config.edge_ip_version).is_none() {
+            if let Some(version) = non_empty(&self.edge_ip_version) {
+                config.edge_ip_version = Some(version.to_string());
+            }
+        }
+    }
+}
+
+/// Daily window in local time during which a tunnel should be running
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TunnelSchedule {
+    /// Start time as "HH:MM"
+    pub start: String,
+    /// Stop time as "HH:MM"; earlier than `start` means the window runs past midnight
+    pub stop: String,
+    /// Days the window starts on, 0 = Monday .. 6 = Sunday. Empty means every day.
+    #[serde(default)]
+    pub days: Vec<u32>,
+}
+
+impl TunnelSchedule {
+    fn parse_time(value: &str) -> Result<chrono::NaiveTime, String> {
+        chrono::NaiveTime::parse_from_str(value.trim(), "%H:%M")
+            .map_err(|_| format!("Invalid schedule time '{}', expected HH:MM", value))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let start = Self::parse_time(&self.start)?;
+        let stop = Self::parse_time(&self.stop)?;
+        if start == stop {
+            return Err("Schedule start and stop times must differ".to_string());
+        }
+        if let Some(day) = self.days.iter().find(|d| **d > 6) {
+            return Err(format!("Invalid schedule day {}, expected 0 (Monday) to 6 (Sunday)", day));
+        }
+        Ok(())
+    }
+
+    /// Whether `now` (local time) falls inside the window
+    pub fn is_active_at(&self, now: chrono::NaiveDateTime) -> bool {
+        use chrono::{Datelike, Duration};
+
+        let (Ok(start), Ok(stop)) = (Self::parse_time(&self.start), Self::parse_time(&self.stop)) else {
+            return false;
+        };
+        let runs_on = |date: chrono::NaiveDate| {
+            self.days.is_empty() || self.days.contains(&date.weekday().num_days_from_monday())
+        };
+        let today = now.date();
+        let time = now.time();
+        if start < stop {
+            runs_on(today) && time >= start && time < stop
+        } else {
+            // Overnight window: either it started today, or it started yesterday and hasn't ended
+            (runs_on(today) && time >= start) || (runs_on(today - Duration::days(1)) && time < stop)
+        }
+    }
+}
+
+fn default_local_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+impl CloudflareConfig {
+    /// A new config with a fresh id and the same defaults a deserialized one gets
+    pub fn new(name: String, tunnel_token: String, local_port: u16) -> Self {
+        Self {
+            id: crate::types::amp::generate_uuid(),
+            name,
+            tunnel_token,
+            local_port,
+            local_host: default_local_host(),
+            enabled: false,
+            auto_start: false,
+            metrics_addr: None,
+            metrics_expose: false,
+            post_quantum: false,
+            no_autoupdate: default_no_autoupdate(),
+            shutdown_grace_period_secs: default_shutdown_grace_period_secs(),
+            connect_timeout_secs: default_connect_timeout_secs(),
+            restart_on_connect_timeout: false,
+            grace_period_secs: None,
+            idle_timeout_secs: None,
+            confirm_expose: false,
+            check_local_port: default_check_local_port(),
+            check_port_owner: false,
+            auto_reconnect: default_auto_reconnect(),
+            log_to_file: false,
+            extra_args: Vec::new(),
+            env: HashMap::new(),
+            egress_proxy: None,
+            use_system_proxy: false,
+            stream_logs: false,
+            schedule: None,
+            status_webhook: None,
+            edge_preflight: false,
+            edge_ip_version: None,
+            origin_protocol: TunnelProtocol::Http,
+            unix_socket: None,
+            origin_server_name: None,
+            no_tls_verify: false,
+            http_host_header: None,
+            ingress: Vec::new(),
+            tags: Vec::new(),
+            config_file: None,
+            watch_config_file: false,
+            working_dir: None,
+            access: None,
+            dns_proxy: None,
+        }
+    }
+
+    /// The host cloudflared should dial for the origin, without IPv6 brackets.
+    /// "localhost" is pinned to IPv4, which is what most dev servers bind.
+    pub fn origin_host(&self) -> String {
+        let host = self.local_host.trim();
+        let host = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host);
+        if host.is_empty() || host.eq_ignore_ascii_case("localhost") {
+            "127.0.0.1".to_string()
+        } else {
+            host.to_string()
+        }
+    }
+
+    /// The `--url` cloudflared serves for a quick tunnel
+    pub fn origin_url(&self) -> String {
+        let host = self.origin_host();
+        let host = if host.contains(':') { format!("[{}]", host) } else { host };
+        match self.origin_protocol {
+            TunnelProtocol::Http => format!("http://{}:{}", host, self.local_port),
+            TunnelProtocol::Https => format!("https://{}:{}", host, self.local_port),
+            TunnelProtocol::Unix => format!("unix:{}", self.unix_socket.as_deref().unwrap_or("").trim()),
+        }
+    }
+
+    /// Whether this tunnel is tagged `tag`, ignoring case and surrounding spaces
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = tag.trim();
+        self.tags.iter().any(|t| t.trim().eq_ignore_ascii_case(tag))
+    }
+
+    /// `ingress` in order, ending with a catch-all. Empty when no rules are configured.
+    pub fn ingress_rules(&self) -> Vec<IngressRule> {
+        let mut rules = self.ingress.clone();
+        if rules.last().is_some_and(|rule| rule.hostname().is_some()) {
+            rules.push(IngressRule {
+                hostname: None,
+                service: INGRESS_CATCH_ALL_SERVICE.to_string(),
+            });
+        }
+        rules
+    }
+
+    /// `metrics_addr` as host and port; None when unset, Err when it isn't host:port.
+    /// An empty host (":2000") binds every interface, as it does for 0.0.0.0.
+    fn metrics_bind(&self) -> Option<Result<(String, u16), String>> {
+        let addr = self.metrics_addr.as_deref().map(str::trim).filter(|a| !a.is_empty())?;
+        let invalid = || format!("Metrics address '{}' must be host:port, e.g. 127.0.0.1:49312", addr);
+        let Some((host, port)) = addr.rsplit_once(':') else {
+            return Some(Err(invalid()));
+        };
+        let host = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host);
+        if host.contains("://") || host.contains('/') || host.chars().any(char::is_whitespace) {
+            return Some(Err(invalid()));
+        }
+        match port.parse::<u16>() {
+            Ok(port) => Some(Ok((host.to_string(), port))),
+            Err(_) => Some(Err(invalid())),
+        }
+    }
+
+    /// Whether the metrics server would listen beyond this machine
+    pub fn metrics_exposed(&self) -> bool {
+        match self.metrics_bind() {
+            Some(Ok((host, _))) => {
+                let loopback = host.eq_ignore_ascii_case("localhost")
+                    || host.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback());
+                !loopback
+            }
+            _ => false,
+        }
+    }
+
+    /// Strip whitespace users commonly paste along with the token
+    pub fn normalize(&mut self) {
+        self.tunnel_token = self.tunnel_token.trim().to_string();
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.to_string());
+            }
+        }
+        self.tags = tags;
+    }
+
+    /// Copy safe to share in bug reports - the token is a long-lived credential
+    pub fn redacted(&self) -> CloudflareConfig {
+        let mut config = self.clone();
+        if !config.tunnel_token.is_empty() {
+            config.tunnel_token = "***".to_string();
+        }
+        // Env often carries proxy credentials; keep the names, hide the values
+        for value in config.env.values_mut() {
+            *value = "***".to_string();
+        }
+        if config.egress_proxy.is_some() {
+            config.egress_proxy = Some("***".to_string());
+        }
+        // Webhook URLs embed their own secret
+        if config.status_webhook.is_some() {
+            config.status_webhook = Some("***".to_string());
+        }
+        config
+    }
+
+    /// Whether switching from `self` to `other` needs a cloudflared respawn: only when
+    /// something that feeds the spawn changed (the command line and environment, the
+    /// origin, the generated ingress, the system proxy lookup, the log file). Supervisor
+    /// settings such as timeouts and retries apply without one.
+    pub fn requires_restart(&self, other: &CloudflareConfig) -> bool {
+        let spawn_inputs = |c: &CloudflareConfig| {
+            (
+                (&c.tunnel_token, c.origin_url(), c.ingress_rules(), &c.config_file, &c.working_dir),
+                (&c.metrics_addr, c.post_quantum, c.no_autoupdate, c.grace_period_secs, &c.edge_ip_version),
+                (&c.extra_args, &c.env, &c.egress_proxy, c.use_system_proxy, c.log_to_file),
+                (&c.origin_server_name, &c.http_host_header, c.no_tls_verify, &c.access, &c.dns_proxy),
+            )
+        };
+        spawn_inputs(self) != spawn_inputs(other)
+    }
+
+    /// Reject configs cloudflared would fail on with an opaque error
+    pub fn validate(&self) -> Result<(), String> {
+        let token = self.tunnel_token.trim();
+        if token.starts_with("cloudflared ") {
+            return Err("Paste only the token, not the whole cloudflared command - it's the long string after `--token` or `service install`.".to_string());
+        }
+        if token.chars().any(char::is_whitespace) {
+            return Err("Tunnel token must not contain spaces or line breaks. Copy it again from the Cloudflare dashboard.".to_string());
+        }
+        if !token.is_empty() && !looks_like_tunnel_token(token) {
+            return Err("This doesn't look like a tunnel token. Copy the token (not the tunnel name or ID) from Zero Trust dashboard > Networks > Tunnels.".to_string());
+        }
+        if let Some(schedule) = &self.schedule {
+            schedule.validate()?;
+        }
+        if let Some(version) = self.edge_ip_version.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+            if !matches!(version, "4" | "6" | "auto") {
+                return Err(format!("Invalid edge IP version '{}' - use 4, 6 or auto", version));
+            }
+        }
+        if let Some(grace) = self.grace_period_secs {
+            if grace >= self.shutdown_grace_period_secs {
+                return Err(format!(
+                    "cloudflared's grace period ({}s) must be shorter than the shutdown timeout ({}s), or it's killed before it finishes draining",
+                    grace, self.shutdown_grace_period_secs
+                ));
+            }
+        }
+        if !self.ingress.is_empty() && token.is_empty() {
+            return Err("Ingress rules need a named tunnel - quick tunnels serve a single origin".to_string());
+        }
+        for (i, rule) in self.ingress.iter().enumerate() {
+            if rule.service.trim().is_empty() {
+                return Err(format!("Ingress rule {} needs a service, e.g. http://localhost:8080", i + 1));
+            }
+            if rule.hostname().is_none() && i + 1 != self.ingress.len() {
+                return Err(format!(
+                    "Ingress rule {} has no hostname, so it catches every request - move it to the end",
+                    i + 1
+                ));
+            }
+        }
+        if let Some(Err(e)) = self.metrics_bind() {
+            return Err(e);
+        }
+        if self.metrics_exposed() && !self.metrics_expose {
+            return Err(format!(
+                "Metrics address '{}' is reachable from other machines. Use 127.0.0.1, or allow exposing metrics if you mean to scrape them remotely.",
+                self.metrics_addr.as_deref().unwrap_or("").trim()
+            ));
+        }
+        let config_file = self.config_file.as_deref().map(str::trim).unwrap_or("");
+        if !config_file.is_empty() && !self.ingress.is_empty() {
+            return Err("Use either ingress rules or an external config file, not both".to_string());
+        }
+        if self.watch_config_file && config_file.is_empty() {
+            return Err("Choose a config file to watch".to_string());
+        }
+        if let Some(idle) = self.idle_timeout_secs {
+            if idle == 0 {
+                return Err("Idle timeout must be at least 1 second - leave it empty to turn it off".to_string());
+            }
+            if !token.is_empty() {
+                return Err("Idle timeout only applies to quick tunnels".to_string());
+            }
+            if self.metrics_bind().is_none() {
+                return Err("Idle timeout needs a metrics address so requests can be counted".to_string());
+            }
+        }
+        if let Some(dir) = self.working_dir.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+            if !std::path::Path::new(dir).is_absolute() {
+                return Err(format!("Working directory '{}' must be absolute", dir));
+            }
+        }
+        if let Some(name) = self.origin_server_name.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+            if !is_plausible_hostname(name) {
+                return Err(format!("Origin server name '{}' must be a hostname like app.internal.example.com", name));
+            }
+        }
+        if let Some(host) = self.http_host_header.as_deref().map(str::trim).filter(|h| !h.is_empty()) {
+            // Visible ASCII only: anything else is either an invalid header value or would
+            // let the value smuggle in a second header
+            if host.contains('/') || !host.chars().all(|c| c.is_ascii_graphic()) {
+                return Err(format!("Host header '{}' must be a host with an optional port, like app.local:3000", host));
+            }
+        }
+        if self.origin_protocol == TunnelProtocol::Unix {
+            let path = self.unix_socket.as_deref().map(str::trim).unwrap_or("");
+            if path.is_empty() {
+                return Err("Enter the socket path for a Unix socket origin, e.g. /var/run/app.sock".to_string());
+            }
+            if !std::path::Path::new(path).is_absolute() {
+                return Err(format!("Socket path '{}' must be absolute", path));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Loose shape check for tunnel tokens: base64 of a JSON object, so a long run of
+/// base64 characters that starts with "eyJ" ({"). Catches tunnel names and UUIDs
+/// pasted into the token field without trying to fully decode the token.
+fn looks_like_tunnel_token(token: &str) -> bool {
+    token.len() >= 64
+        && token.starts_with("eyJ")
+        && token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=' | '-' | '_'))
+}
+
+/// Dot-separated labels of letters, digits and inner hyphens, within DNS length limits
+fn is_plausible_hostname(name: &str) -> bool {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+fn default_no_autoupdate() -> bool {
+    true
+}
+
+fn default_shutdown_grace_period_secs() -> u64 {
+    10
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    60
+}
+
+fn default_check_local_port() -> bool {
+    true
+}
+
+fn default_auto_reconnect() -> bool {
+    true
 }
@@ -1516,10 +1516,11 @@ export async function deleteCloudflareConfig(
 	return invoke("delete_cloudflare_config", { id });
 }
 
+// Resolves to false when disabling a tunnel that wasn't running
 export async function setCloudflareConnection(
 	id: string,
 	enable: boolean,
-): Promise<void> {
+): Promise<boolean> {
 	return invoke("set_cloudflare_connection", { id, enable });
 }
 